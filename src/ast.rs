@@ -1,21 +1,200 @@
-use crate::error::EvalError;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 
+use crate::error::{DivisorKind, EvalError, OpKind};
+use crate::function;
+use crate::machine::Walk;
+
+/// Hooks called by [`Expr::eval_with_observer`] while it walks a tree.
+///
+/// Both hooks have no-op defaults, so an observer only implements the ones
+/// it cares about.
+pub trait EvalObserver {
+    /// Called before a node's operands are evaluated. Returning an error
+    /// aborts the evaluation with that error.
+    fn on_enter(&mut self, _expr: &Expr) -> Result<(), EvalError> {
+        Ok(())
+    }
+
+    /// Called after a node has evaluated successfully.
+    fn on_result(&mut self, _expr: &Expr, _value: f64) {}
+}
+
+/// What evaluation does when an operation produces NaN.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NanMode {
+    /// Fail with `EvalError::NotANumber`, so that e.g. `(-8) ^ 0.5` is an
+    /// error rather than a NaN result. A function applied outside its
+    /// domain, as in `sqrt(-1)`, fails with `EvalError::DomainError`.
+    #[default]
+    Error,
+    /// Let NaN flow through to the result as IEEE 754 does, including from
+    /// functions applied outside their domain.
+    Propagate,
+}
+
+/// What evaluation does when a quotient has a zero divisor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DivByZeroMode {
+    /// Fail with `EvalError::DivisionByZero`.
+    #[default]
+    Error,
+    /// Return the IEEE 754 quotient: infinity of the appropriate sign, or
+    /// NaN for `0 / 0`. The quotient is returned as is, bypassing the NaN
+    /// and overflow checks, but later operations still check their
+    /// results: `1 / 0 + 1` is an overflow. Pair this with
+    /// `EvalConfig::allow_overflow` and `NanMode::Propagate` to let the
+    /// infinity or NaN flow through the rest of the expression.
+    Ieee,
+}
+
+/// The binary arithmetic operators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+impl From<BinOp> for OpKind {
+    fn from(op: BinOp) -> Self {
+        match op {
+            BinOp::Add => OpKind::Add,
+            BinOp::Sub => OpKind::Sub,
+            BinOp::Mul => OpKind::Mul,
+            BinOp::Div => OpKind::Div,
+            BinOp::Pow => OpKind::Pow,
+        }
+    }
+}
+
+/// A replacement implementation of a binary operator.
+pub type BinOpFn = fn(f64, f64) -> Result<f64, EvalError>;
+
+/// Options for [`Expr::eval_with_config`]. The default matches `eval`.
+#[derive(Debug, Clone, Default)]
+pub struct EvalConfig {
+    pub nan: NanMode,
+    pub div_by_zero: DivByZeroMode,
+    /// Let results overflow to infinity and underflow to zero or a
+    /// subnormal, as IEEE 754 does, instead of failing with
+    /// `EvalError::Overflow` or `EvalError::Underflow`. Infinite literals
+    /// and variable values are allowed too.
+    pub allow_overflow: bool,
+    /// Take the arguments of `sin`, `cos` and `tan`, and give the results
+    /// of `asin` and `acos`, in degrees rather than radians.
+    pub trig_degrees: bool,
+    /// Reject the powers `powf` quietly resolves: `0 ^ 0` (which `powf`
+    /// calls 1), `0` to a negative power (infinity) and a negative base to
+    /// a non-integer power (NaN) fail with `EvalError::DomainError` instead.
+    pub strict_pow: bool,
+    /// Custom implementations that replace the built-in arithmetic of
+    /// their operator, including its zero-divisor and `strict_pow` checks.
+    /// Their results are still subject to the NaN and overflow checks, but
+    /// only a subnormal result counts as underflow.
+    pub overrides: HashMap<BinOp, BinOpFn>,
+    /// Snap the final result to the shortest decimal within this distance
+    /// of it, so that `0.1 + 0.2` gives `0.3` rather than
+    /// `0.30000000000000004`. Intermediate results are not snapped.
+    pub snap_epsilon: Option<f64>,
+    /// Replace every operation's result smaller in magnitude than this
+    /// threshold with `0.0`, intermediate results included. Flushed
+    /// results are never reported as underflow.
+    pub flush_to_zero: Option<f64>,
+}
+
+/// Another name for [`EvalConfig`]: the two are the same type. New code
+/// should use `EvalConfig`.
+#[deprecated(note = "use EvalConfig, which is the same type")]
+pub type EvalOptions = EvalConfig;
+
+pub(crate) struct NoopObserver;
+
+impl EvalObserver for NoopObserver {}
+
+/// Aborts evaluation once the shared flag is set.
+struct CancelObserver<'c> {
+    cancel: &'c AtomicBool,
+}
+
+impl EvalObserver for CancelObserver<'_> {
+    fn on_enter(&mut self, _expr: &Expr) -> Result<(), EvalError> {
+        if self.cancel.load(Ordering::Relaxed) {
+            Err(EvalError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// One node of the annotated tree returned by [`Expr::eval_tree`].
 #[derive(Debug, Clone)]
+pub struct EvalNode {
+    pub expr_string: String,
+    pub value: Result<f64, EvalError>,
+    pub children: Vec<EvalNode>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
     Number(f64),
+    /// A variable, looked up in the environment passed to
+    /// [`Expr::eval_with`].
+    Var(String),
     Add(Box<Expr>, Box<Expr>),
     Sub(Box<Expr>, Box<Expr>),
     Mul(Box<Expr>, Box<Expr>),
     Div(Box<Expr>, Box<Expr>),
     Neg(Box<Expr>),
     Pow(Box<Expr>, Box<Expr>),
+    /// The double factorial `n!!`, the product of the positive integers up
+    /// to `n` with the same parity as `n`. Defined for non-negative
+    /// integers, with `0!! = 1`.
+    DoubleFactorial(Box<Expr>),
+    /// The bounds of a range such as `0..10`. A range is not a number:
+    /// `eval` rejects it, and [`Expr::eval_range`] evaluates its bounds.
+    Range(Box<Expr>, Box<Expr>),
+    /// A call of a built-in function, such as `sqrt(16)`.
+    Call(String, Vec<Expr>),
+    /// A local binding, `let name = value in body`: `body` is evaluated
+    /// with `name` bound to the value of `value`. The binding is only
+    /// visible in `body`, where it shadows any outer variable of the same
+    /// name.
+    Let {
+        name: String,
+        value: Box<Expr>,
+        body: Box<Expr>,
+    },
+}
+
+/// The default expression is the literal `0`, the additive identity, so a
+/// defaulted `Expr` evaluates to `0.0` and is a harmless placeholder for
+/// `std::mem::take`-style rewrites.
+impl Default for Expr {
+    fn default() -> Self {
+        Expr::Number(0.0)
+    }
 }
 
+// The constructors are named after the nodes they build; `Expr::add(a, b)`
+// takes no `self` and is not a stand-in for `std::ops::Add`.
+#[allow(clippy::should_implement_trait)]
 impl Expr {
     pub fn number(n: f64) -> Expr {
         Expr::Number(n)
     }
 
+    pub fn var(name: impl Into<String>) -> Expr {
+        Expr::Var(name.into())
+    }
+
+    pub fn call(name: impl Into<String>, args: Vec<Expr>) -> Expr {
+        Expr::Call(name.into(), args)
+    }
+
     pub fn add(a: Expr, b: Expr) -> Expr {
         Expr::Add(Box::new(a), Box::new(b))
     }
@@ -32,73 +211,607 @@ impl Expr {
         Expr::Div(Box::new(a), Box::new(b))
     }
 
+    /// Negates `a`, collapsing a directly nested negation so that
+    /// `Expr::neg(Expr::neg(x))` is just `x`.
     pub fn neg(a: Expr) -> Expr {
-        Expr::Neg(Box::new(a))
+        match a {
+            Expr::Neg(inner) => *inner,
+            other => Expr::Neg(Box::new(other)),
+        }
     }
 
     pub fn pow(a: Expr, b: Expr) -> Expr {
         Expr::Pow(Box::new(a), Box::new(b))
     }
 
-    pub fn eval(&self) -> Result<f64, EvalError> {
+    pub fn double_factorial(a: Expr) -> Expr {
+        Expr::DoubleFactorial(Box::new(a))
+    }
+
+    pub fn range(start: Expr, end: Expr) -> Expr {
+        Expr::Range(Box::new(start), Box::new(end))
+    }
+
+    pub fn let_in(name: impl Into<String>, value: Expr, body: Expr) -> Expr {
+        Expr::Let {
+            name: name.into(),
+            value: Box::new(value),
+            body: Box::new(body),
+        }
+    }
+
+    /// Returns the value of a number literal without evaluating anything;
+    /// `None` for every other node.
+    pub fn as_number(&self) -> Option<f64> {
         match self {
-            Expr::Number(n) => Ok(*n),
-            Expr::Add(a, b) => {
-                let result = a.eval()? + b.eval()?;
-                Self::check_result(result)
-            }
-            Expr::Sub(a, b) => {
-                let result = a.eval()? - b.eval()?;
-                Self::check_result(result)
-            }
-            Expr::Mul(a, b) => {
-                let result = a.eval()? * b.eval()?;
-                Self::check_result(result)
-            }
-            Expr::Div(a, b) => {
-                let divisor = b.eval()?;
-                if divisor == 0.0 {
-                    return Err(EvalError::DivisionByZero);
+            Expr::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Sums `terms` as a balanced tree of `Add` nodes, so the tree depth
+    /// grows with `log2(terms.len())` instead of linearly. An empty sum is 0.
+    pub fn balanced_sum(terms: Vec<Expr>) -> Expr {
+        Self::balanced(terms, Expr::add).unwrap_or(Expr::Number(0.0))
+    }
+
+    /// Multiplies `factors` as a balanced tree of `Mul` nodes. An empty
+    /// product is 1.
+    pub fn balanced_product(factors: Vec<Expr>) -> Expr {
+        Self::balanced(factors, Expr::mul).unwrap_or(Expr::Number(1.0))
+    }
+
+    fn balanced(mut operands: Vec<Expr>, combine: fn(Expr, Expr) -> Expr) -> Option<Expr> {
+        match operands.len() {
+            0 => None,
+            1 => operands.pop(),
+            len => {
+                let right = operands.split_off(len / 2);
+                let left = Self::balanced(operands, combine)?;
+                let right = Self::balanced(right, combine)?;
+                Some(combine(left, right))
+            }
+        }
+    }
+
+    /// Drops the tree with an explicit stack rather than recursion. A tree
+    /// nested tens of thousands of levels deep can overflow the call stack
+    /// when dropped normally; release one through this instead.
+    pub fn drop_iteratively(self) {
+        let mut pending = vec![self];
+        while let Some(expr) = pending.pop() {
+            match expr {
+                Expr::Number(_) | Expr::Var(_) => {}
+                Expr::Neg(a) | Expr::DoubleFactorial(a) => pending.push(*a),
+                Expr::Add(a, b)
+                | Expr::Sub(a, b)
+                | Expr::Mul(a, b)
+                | Expr::Div(a, b)
+                | Expr::Pow(a, b)
+                | Expr::Range(a, b)
+                | Expr::Let {
+                    value: a, body: b, ..
+                } => {
+                    pending.push(*a);
+                    pending.push(*b);
                 }
-                let result = a.eval()? / divisor;
-                Self::check_result(result)
+                Expr::Call(_, args) => pending.extend(args),
+            }
+        }
+    }
+
+    /// Returns the immediate subexpressions of this node, left to right.
+    pub fn children(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Number(_) | Expr::Var(_) => Vec::new(),
+            Expr::Neg(a) | Expr::DoubleFactorial(a) => vec![a],
+            Expr::Add(a, b)
+            | Expr::Sub(a, b)
+            | Expr::Mul(a, b)
+            | Expr::Div(a, b)
+            | Expr::Pow(a, b)
+            | Expr::Range(a, b) => vec![a, b],
+            Expr::Call(_, args) => args.iter().collect(),
+            Expr::Let { value, body, .. } => vec![value, body],
+        }
+    }
+
+    /// Rebuilds this node around new children, given in the order
+    /// `children` returns them.
+    pub(crate) fn with_children(&self, mut children: Vec<Expr>) -> Expr {
+        let mut next = || children.remove(0);
+        match self {
+            Expr::Number(_) | Expr::Var(_) => self.clone(),
+            Expr::Neg(_) => Expr::neg(next()),
+            Expr::DoubleFactorial(_) => Expr::double_factorial(next()),
+            Expr::Add(..) => Expr::add(next(), next()),
+            Expr::Sub(..) => Expr::sub(next(), next()),
+            Expr::Mul(..) => Expr::mul(next(), next()),
+            Expr::Div(..) => Expr::div(next(), next()),
+            Expr::Pow(..) => Expr::pow(next(), next()),
+            Expr::Range(..) => Expr::range(next(), next()),
+            Expr::Call(name, _) => Expr::call(name.clone(), children),
+            Expr::Let { name, .. } => Expr::let_in(name.clone(), next(), next()),
+        }
+    }
+
+    /// Returns every number literal in the tree, left to right.
+    pub fn collect_constants(&self) -> Vec<f64> {
+        let mut constants = Vec::new();
+        self.push_constants(&mut constants);
+        constants
+    }
+
+    fn push_constants(&self, constants: &mut Vec<f64>) {
+        match self {
+            Expr::Number(n) => constants.push(*n),
+            _ => {
+                for child in self.children() {
+                    child.push_constants(constants);
+                }
+            }
+        }
+    }
+
+    /// Returns the number of nodes on the longest root-to-leaf path.
+    pub fn depth(&self) -> usize {
+        1 + self
+            .children()
+            .into_iter()
+            .map(Expr::depth)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Evaluates the expression.
+    ///
+    /// Evaluation is deterministic: operands are always evaluated left to
+    /// right, so the same tree yields bit-identical results on every call
+    /// (see [`results_bitwise_equal`]).
+    ///
+    /// Evaluation works at any nesting depth. Most other operations on a
+    /// tree, including dropping it, `clone`, `==`, `Debug` and `Display`,
+    /// recurse, and can overflow the stack on a tree nested tens of
+    /// thousands of levels deep; drop such a tree with
+    /// [`Expr::drop_iteratively`].
+    ///
+    /// No variables are defined; use [`Expr::eval_with`] to supply them.
+    pub fn eval(&self) -> Result<f64, EvalError> {
+        self.eval_with(&HashMap::new())
+    }
+
+    /// Evaluates the expression, looking variables up in `env`. A variable
+    /// missing from `env` fails with `EvalError::UndefinedVariable`.
+    pub fn eval_with(&self, env: &HashMap<String, f64>) -> Result<f64, EvalError> {
+        self.evaluate(env, &EvalConfig::default(), &mut NoopObserver)
+    }
+
+    /// Evaluates the expression and rounds the result to at most
+    /// `sig_digits` significant digits (at least one). The flag reports
+    /// whether rounding changed the value.
+    pub fn eval_rounded(&self, sig_digits: usize) -> Result<(f64, bool), EvalError> {
+        let value = self.eval()?;
+        let rounded = round_significant(value, sig_digits.max(1));
+        Ok((rounded, rounded != value))
+    }
+
+    /// Evaluates the bounds of a range expression such as `0..10`, giving
+    /// `(0.0, 10.0)`. Any other expression is the one-value range from its
+    /// value to itself.
+    pub fn eval_range(&self) -> Result<(f64, f64), EvalError> {
+        self.eval_range_in(&HashMap::new())
+    }
+
+    // A `let` whose body is a range, like `let n = 3 in 0..n`, is a range
+    // too.
+    fn eval_range_in(&self, env: &HashMap<String, f64>) -> Result<(f64, f64), EvalError> {
+        match self {
+            Expr::Range(start, end) => Ok((start.eval_with(env)?, end.eval_with(env)?)),
+            Expr::Let { name, value, body } => {
+                let value = value.eval_with(env)?;
+                body.eval_range_in(&Self::bind(env, name, value))
             }
-            Expr::Neg(a) => {
-                let result = -a.eval()?;
-                Self::check_result(result)
+            _ => {
+                let value = self.eval_with(env)?;
+                Ok((value, value))
             }
-            Expr::Pow(a, b) => {
-                let result = a.eval()?.powf(b.eval()?);
-                Self::check_result(result)
+        }
+    }
+
+    /// Evaluates the expression with the semantics selected by `config`.
+    pub fn eval_with_config(&self, config: &EvalConfig) -> Result<f64, EvalError> {
+        let value = self.evaluate(&HashMap::new(), config, &mut NoopObserver)?;
+        Ok(match config.snap_epsilon {
+            Some(epsilon) => snap_to_decimal(value, epsilon),
+            None => value,
+        })
+    }
+
+    /// The same as [`Expr::eval_with_config`], which new code should use.
+    #[deprecated(note = "use eval_with_config, which behaves identically")]
+    #[allow(deprecated)]
+    pub fn eval_with_options(&self, opts: &EvalOptions) -> Result<f64, EvalError> {
+        self.eval_with_config(opts)
+    }
+
+    /// Evaluates the expression, reporting every visited node to `obs`.
+    pub fn eval_with_observer(&self, obs: &mut impl EvalObserver) -> Result<f64, EvalError> {
+        self.evaluate(&HashMap::new(), &EvalConfig::default(), obs)
+    }
+
+    /// Evaluates the expression, checking `cancel` before each node and
+    /// failing with `EvalError::Cancelled` once another thread sets it.
+    pub fn eval_with_cancel(&self, cancel: &AtomicBool) -> Result<f64, EvalError> {
+        self.eval_with_observer(&mut CancelObserver { cancel })
+    }
+
+    /// Evaluates with an explicit stack rather than recursion, so deeply
+    /// nested trees cannot overflow the call stack.
+    fn evaluate(
+        &self,
+        env: &HashMap<String, f64>,
+        config: &EvalConfig,
+        obs: &mut impl EvalObserver,
+    ) -> Result<f64, EvalError> {
+        Walk::new(self, Cow::Borrowed(env), Cow::Borrowed(config)).run(obs)
+    }
+
+    /// Computes this node's value from the values of its children, given in
+    /// order. Leaves take no operands.
+    pub(crate) fn apply_node(
+        &self,
+        operands: &[f64],
+        env: &HashMap<String, f64>,
+        config: &EvalConfig,
+    ) -> Result<f64, EvalError> {
+        match self {
+            Expr::Number(n) => Self::check_literal(*n, config),
+            Expr::Var(name) => Self::lookup(env, name, config),
+            Expr::Neg(_) | Expr::DoubleFactorial(_) => self.apply_unary(operands[0], config),
+            Expr::Call(name, _) => Self::apply_call(name, operands, config),
+            Expr::Let { .. } => unreachable!("a let binds before its body is evaluated"),
+            _ => self.apply_binary(operands[0], operands[1], config),
+        }
+    }
+
+    /// Applies this node's unary operator to its evaluated operand.
+    fn apply_unary(&self, value: f64, config: &EvalConfig) -> Result<f64, EvalError> {
+        match self {
+            Expr::Neg(_) => Self::check_result(-value, Some(OpKind::Neg), value, None, config),
+            Expr::DoubleFactorial(_) => {
+                if value < 0.0 || value.fract() != 0.0 {
+                    return Err(EvalError::DomainError(format!(
+                        "double factorial of {}, which is not a non-negative integer",
+                        value
+                    )));
+                }
+                // Stops early once the product overflows, so a huge `n`
+                // does not loop for long
+                let mut result: f64 = 1.0;
+                let mut factor = value;
+                while factor > 1.0 && result.is_finite() {
+                    result *= factor;
+                    factor -= 2.0;
+                }
+                Self::check_result(result, Some(OpKind::DoubleFactorial), value, None, config)
             }
+            _ => unreachable!("not a unary operator: {:?}", self),
+        }
+    }
+
+    /// The operator of an arithmetic binary node.
+    pub fn bin_op(&self) -> Option<BinOp> {
+        match self {
+            Expr::Add(..) => Some(BinOp::Add),
+            Expr::Sub(..) => Some(BinOp::Sub),
+            Expr::Mul(..) => Some(BinOp::Mul),
+            Expr::Div(..) => Some(BinOp::Div),
+            Expr::Pow(..) => Some(BinOp::Pow),
+            _ => None,
         }
     }
 
-    fn check_result(result: f64) -> Result<f64, EvalError> {
-        if result.is_infinite() {
-            if result.is_sign_positive() {
-                Err(EvalError::Overflow)
-            } else {
-                Err(EvalError::Underflow)
+    /// Applies this node's binary operator to its evaluated operands.
+    fn apply_binary(&self, left: f64, right: f64, config: &EvalConfig) -> Result<f64, EvalError> {
+        let bin_op = self.bin_op();
+        let op = bin_op.map(OpKind::from);
+        if let Some(custom) = bin_op.and_then(|bin_op| config.overrides.get(&bin_op)) {
+            let result = custom(left, right)?;
+            // The override's semantics are unknown, so its operator is not
+            // reported: that keeps a zero result from being inferred to be
+            // an underflow of the built-in operator.
+            return Self::check_result(result, None, left, Some(right), config);
+        }
+        let result = match self {
+            Expr::Add(..) => left + right,
+            Expr::Sub(..) => left - right,
+            Expr::Mul(..) => left * right,
+            Expr::Div(..) if right == 0.0 && config.div_by_zero == DivByZeroMode::Ieee => {
+                return Ok(left / right);
+            }
+            Expr::Div(_, divisor) => {
+                if right == 0.0 {
+                    let kind = if divisor.is_literal() {
+                        DivisorKind::Literal
+                    } else {
+                        DivisorKind::Computed
+                    };
+                    return Err(EvalError::DivisionByZero {
+                        kind,
+                        dividend: left,
+                    });
+                }
+                left / right
+            }
+            Expr::Pow(..) => {
+                if config.strict_pow {
+                    Self::check_pow_domain(left, right)?;
+                }
+                left.powf(right)
             }
+            Expr::Range(..) => return Err(EvalError::NotAScalar),
+            _ => unreachable!("not a binary operator: {:?}", self),
+        };
+        Self::check_result(result, op, left, Some(right), config)
+    }
+
+    /// Calls the built-in `name` on its evaluated arguments.
+    fn apply_call(name: &str, args: &[f64], config: &EvalConfig) -> Result<f64, EvalError> {
+        let result = function::call(name, args, config.trig_degrees, config.nan)?;
+        let lhs = args.first().copied().unwrap_or(f64::NAN);
+        Self::check_result(
+            result,
+            Some(OpKind::Call),
+            lhs,
+            args.get(1).copied(),
+            config,
+        )
+    }
+
+    fn check_pow_domain(base: f64, exponent: f64) -> Result<(), EvalError> {
+        let message = if base == 0.0 && exponent == 0.0 {
+            "0 raised to the power 0"
+        } else if base == 0.0 && exponent < 0.0 {
+            "0 raised to a negative power"
+        } else if base < 0.0 && exponent.fract() != 0.0 {
+            "negative base with non-integer exponent"
         } else {
-            Ok(result)
+            return Ok(());
+        };
+        Err(EvalError::DomainError(message.to_string()))
+    }
+
+    /// Evaluates every node of the tree, returning the value (or error) of
+    /// each subexpression alongside its children.
+    ///
+    /// Unlike `eval`, all operands are evaluated even if one fails; a node
+    /// whose operand failed reports the first failing operand's error.
+    pub fn eval_tree(&self) -> EvalNode {
+        self.eval_tree_in(&HashMap::new())
+    }
+
+    fn eval_tree_in(&self, env: &HashMap<String, f64>) -> EvalNode {
+        let config = EvalConfig::default();
+        let (value, children) = match self {
+            Expr::Number(n) => (Self::check_literal(*n, &config), Vec::new()),
+            Expr::Var(name) => (Self::lookup(env, name, &config), Vec::new()),
+            Expr::Neg(a) | Expr::DoubleFactorial(a) => {
+                let operand = a.eval_tree_in(env);
+                let value = operand
+                    .value
+                    .clone()
+                    .and_then(|v| self.apply_unary(v, &config));
+                (value, vec![operand])
+            }
+            Expr::Add(a, b)
+            | Expr::Sub(a, b)
+            | Expr::Mul(a, b)
+            | Expr::Div(a, b)
+            | Expr::Pow(a, b)
+            | Expr::Range(a, b) => {
+                let left = a.eval_tree_in(env);
+                let right = b.eval_tree_in(env);
+                let value = match (&left.value, &right.value) {
+                    (Ok(l), Ok(r)) => self.apply_binary(*l, *r, &config),
+                    (Err(e), _) | (_, Err(e)) => Err(e.clone()),
+                };
+                (value, vec![left, right])
+            }
+            Expr::Call(name, args) => {
+                let children: Vec<EvalNode> =
+                    args.iter().map(|arg| arg.eval_tree_in(env)).collect();
+                let value = children
+                    .iter()
+                    .map(|child| child.value.clone())
+                    .collect::<Result<Vec<_>, _>>()
+                    .and_then(|values| Self::apply_call(name, &values, &config));
+                (value, children)
+            }
+            // The body is evaluated without the binding if the value failed
+            Expr::Let { name, value, body } => {
+                let bound = value.eval_tree_in(env);
+                let body = match &bound.value {
+                    Ok(v) => body.eval_tree_in(&Self::bind(env, name, *v)),
+                    Err(_) => body.eval_tree_in(env),
+                };
+                let value = bound.value.clone().and(body.value.clone());
+                (value, vec![bound, body])
+            }
+        };
+
+        EvalNode {
+            expr_string: self.to_string(),
+            value,
+            children,
         }
     }
 
-    pub fn to_string(&self) -> String {
+    /// Whether the expression is a number literal, possibly negated.
+    pub(crate) fn is_literal(&self) -> bool {
         match self {
-            Expr::Number(n) => n.to_string(),
-            Expr::Add(a, b) => format!("({}) + ({})", a.to_string(), b.to_string()),
-            Expr::Sub(a, b) => format!("({}) - ({})", a.to_string(), b.to_string()),
-            Expr::Mul(a, b) => format!("({}) * ({})", a.to_string(), b.to_string()),
-            Expr::Div(a, b) => format!("({}) / ({})", a.to_string(), b.to_string()),
-            Expr::Neg(a) => format!("-({})", a.to_string()),
-            Expr::Pow(a, b) => format!("({}) ^ ({})", a.to_string(), b.to_string()),
+            Expr::Number(_) => true,
+            Expr::Neg(a) => a.is_literal(),
+            _ => false,
+        }
+    }
+
+    /// Rejects NaN literals, which no parsed input can produce, so a
+    /// malformed hand-built tree fails instead of leaking NaN; this holds
+    /// regardless of the configured `NanMode`. An infinite literal, such as
+    /// `1e400`, is an overflow unless `allow_overflow` is set.
+    fn check_literal(n: f64, config: &EvalConfig) -> Result<f64, EvalError> {
+        if n.is_nan() {
+            Err(EvalError::NotANumber)
+        } else if n.is_infinite() && !config.allow_overflow {
+            Err(EvalError::Overflow {
+                op: None,
+                lhs: n,
+                rhs: None,
+            })
+        } else {
+            Ok(n)
+        }
+    }
+
+    /// Reads a variable's value, which is held to the same rules as a
+    /// literal.
+    fn lookup(
+        env: &HashMap<String, f64>,
+        name: &str,
+        config: &EvalConfig,
+    ) -> Result<f64, EvalError> {
+        match env.get(name) {
+            Some(value) => Self::check_literal(*value, config),
+            None => Err(EvalError::UndefinedVariable(name.to_string())),
+        }
+    }
+
+    /// Returns a copy of `env` with `name` bound to `value`, for the body of
+    /// a `let`.
+    fn bind(env: &HashMap<String, f64>, name: &str, value: f64) -> HashMap<String, f64> {
+        let mut scope = env.clone();
+        scope.insert(name.to_string(), value);
+        scope
+    }
+
+    /// Validates the result of an operation on `lhs` (and `rhs`, for binary
+    /// operations).
+    fn check_result(
+        result: f64,
+        op: Option<OpKind>,
+        lhs: f64,
+        rhs: Option<f64>,
+        config: &EvalConfig,
+    ) -> Result<f64, EvalError> {
+        if result.is_nan() && config.nan == NanMode::Error {
+            return Err(EvalError::NotANumber);
+        }
+        if result.is_infinite() && !config.allow_overflow {
+            return Err(EvalError::Overflow { op, lhs, rhs });
         }
+        if let Some(threshold) = config.flush_to_zero
+            && result.abs() < threshold
+        {
+            return Ok(0.0);
+        }
+        if Self::lost_precision(result, op, lhs, rhs) && !config.allow_overflow {
+            return Err(EvalError::Underflow { op, lhs, rhs });
+        }
+        Ok(result)
+    }
+
+    /// Whether a subnormal or zero result of `op` lost precision, i.e. is an
+    /// underflow. Sums, differences and negations with a subnormal result
+    /// are exact, and a result computed from an already subnormal operand,
+    /// such as `-5e-320`, is not counted as lost precision either.
+    fn lost_precision(result: f64, op: Option<OpKind>, lhs: f64, rhs: Option<f64>) -> bool {
+        if matches!(op, Some(OpKind::Add | OpKind::Sub | OpKind::Neg))
+            || lhs.is_subnormal()
+            || rhs.is_some_and(f64::is_subnormal)
+        {
+            return false;
+        }
+        result.is_subnormal() || (result == 0.0 && Self::nonzero_result(op, lhs, rhs))
+    }
+
+    /// Whether the exact result of `op` on these finite operands is nonzero,
+    /// so that a result of zero can only come from rounding. Sums can cancel
+    /// to zero exactly and functions like `ln` have zeros, so only products,
+    /// quotients and powers are known.
+    fn nonzero_result(op: Option<OpKind>, lhs: f64, rhs: Option<f64>) -> bool {
+        match op {
+            Some(OpKind::Mul | OpKind::Div) => lhs != 0.0 && rhs.is_some_and(|rhs| rhs != 0.0),
+            Some(OpKind::Pow) => lhs != 0.0,
+            _ => false,
+        }
+    }
+}
+
+/// Compares two results by their bit patterns, for reproducibility checks.
+/// Unlike `==`, identical NaNs compare equal and `0.0` differs from `-0.0`.
+pub fn results_bitwise_equal(a: f64, b: f64) -> bool {
+    a.to_bits() == b.to_bits()
+}
+
+/// An expression that may be absent, such as the contents of an empty
+/// input box.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MaybeExpr(pub Option<Expr>);
+
+impl MaybeExpr {
+    /// Evaluates the expression, or returns `0.0` if there is none.
+    pub fn eval_or_zero(&self) -> Result<f64, EvalError> {
+        self.eval_or(0.0)
+    }
+
+    /// Evaluates the expression, or returns `default` if there is none.
+    /// Evaluation errors of a present expression are still reported.
+    pub fn eval_or(&self, default: f64) -> Result<f64, EvalError> {
+        match &self.0 {
+            Some(expr) => expr.eval(),
+            None => Ok(default),
+        }
+    }
+}
+
+impl From<Option<Expr>> for MaybeExpr {
+    fn from(expr: Option<Expr>) -> Self {
+        MaybeExpr(expr)
     }
 }
 
+/// Returns the decimal with the fewest significant digits that lies within
+/// `epsilon` of `value`, or `value` itself if there is none shorter.
+///
+/// Every value is within `epsilon` of a decimal with about
+/// `-log10(epsilon)` decimal places, so the smaller `epsilon`, the fewer
+/// values are changed: with `1e-9`, `1 / 3` snaps to `0.333333333`.
+fn snap_to_decimal(value: f64, epsilon: f64) -> f64 {
+    // 17 significant digits identify every f64, so the search ends there
+    (1..=17)
+        .map(|sig_digits| round_significant(value, sig_digits))
+        .find(|rounded| (rounded - value).abs() <= epsilon)
+        .unwrap_or(value)
+}
+
+fn round_significant(value: f64, sig_digits: usize) -> f64 {
+    if value == 0.0 || !value.is_finite() {
+        return value;
+    }
+    let magnitude = value.abs().log10().floor() as i32;
+    let shift = sig_digits as i32 - 1 - magnitude;
+    // Scale by an exact power of ten on whichever side keeps it an
+    // integer, so the final division or multiplication rounds correctly.
+    let rounded = if shift >= 0 {
+        let scale = 10f64.powi(shift);
+        (value * scale).round() / scale
+    } else {
+        let scale = 10f64.powi(-shift);
+        (value / scale).round() * scale
+    };
+    if rounded.is_finite() { rounded } else { value }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -111,28 +824,604 @@ mod tests {
             Expr::mul(Expr::number(3.0), Expr::number(4.0)),
         );
         let result = expr.eval().expect("Evaluation failed");
-        println!("{} = {}", expr.to_string(), result);
+        println!("{} = {}", expr, result);
         assert_eq!(result, 14.0);
     }
 
+    #[test]
+    fn test_neg_folding() {
+        let x = Expr::add(Expr::number(1.0), Expr::number(2.0));
+        assert_eq!(Expr::neg(Expr::neg(x.clone())), x);
+        assert_eq!(
+            Expr::neg(Expr::neg(Expr::neg(x.clone()))),
+            Expr::Neg(Box::new(x))
+        );
+    }
+
+    #[test]
+    fn test_eval_with_observer() {
+        struct Counter {
+            entered: usize,
+            finished: usize,
+        }
+
+        impl EvalObserver for Counter {
+            fn on_enter(&mut self, _expr: &Expr) -> Result<(), EvalError> {
+                self.entered += 1;
+                Ok(())
+            }
+
+            fn on_result(&mut self, _expr: &Expr, _value: f64) {
+                self.finished += 1;
+            }
+        }
+
+        // 2 + 3 * 4
+        let expr = Expr::add(
+            Expr::number(2.0),
+            Expr::mul(Expr::number(3.0), Expr::number(4.0)),
+        );
+        let mut counter = Counter {
+            entered: 0,
+            finished: 0,
+        };
+        assert_eq!(expr.eval_with_observer(&mut counter).unwrap(), 14.0);
+        assert_eq!(counter.entered, 5);
+        assert_eq!(counter.finished, 5);
+    }
+
+    #[test]
+    fn test_balanced_sum() {
+        let terms = vec![Expr::number(1.0); 1000];
+        let expr = Expr::balanced_sum(terms);
+        assert_eq!(expr.depth(), 11);
+        assert_eq!(expr.eval().unwrap(), 1000.0);
+
+        assert_eq!(Expr::balanced_sum(Vec::new()), Expr::Number(0.0));
+    }
+
+    #[test]
+    fn test_balanced_product() {
+        let factors = vec![Expr::number(2.0); 10];
+        let expr = Expr::balanced_product(factors);
+        assert_eq!(expr.depth(), 5);
+        assert_eq!(expr.eval().unwrap(), 1024.0);
+
+        assert_eq!(Expr::balanced_product(Vec::new()), Expr::Number(1.0));
+    }
+
+    #[test]
+    fn test_nan_modes() {
+        // (-8) ^ 0.5 + 5
+        let expr = Expr::add(
+            Expr::pow(Expr::number(-8.0), Expr::number(0.5)),
+            Expr::number(5.0),
+        );
+
+        let propagate = EvalConfig {
+            nan: NanMode::Propagate,
+            ..EvalConfig::default()
+        };
+        assert!(expr.eval_with_config(&propagate).unwrap().is_nan());
+        // Functions outside their domain give NaN rather than an error
+        let sqrt = Expr::add(
+            Expr::call("sqrt", vec![Expr::number(-1.0)]),
+            Expr::number(5.0),
+        );
+        assert!(sqrt.eval_with_config(&propagate).unwrap().is_nan());
+        assert!(matches!(sqrt.eval(), Err(EvalError::DomainError(_))));
+
+        let error = EvalConfig {
+            nan: NanMode::Error,
+            ..EvalConfig::default()
+        };
+        assert!(matches!(
+            expr.eval_with_config(&error),
+            Err(EvalError::NotANumber)
+        ));
+    }
+
+    #[test]
+    fn test_snap_epsilon() {
+        let expr = Expr::add(Expr::number(0.1), Expr::number(0.2));
+        assert_eq!(
+            expr.eval_with_config(&EvalConfig::default()).unwrap(),
+            0.30000000000000004
+        );
+
+        let snap = EvalConfig {
+            snap_epsilon: Some(1e-9),
+            ..EvalConfig::default()
+        };
+        assert_eq!(expr.eval_with_config(&snap).unwrap(), 0.3);
+        // Every value is within epsilon of some decimal with enough digits
+        let third = Expr::div(Expr::number(1.0), Expr::number(3.0));
+        assert_eq!(third.eval_with_config(&snap).unwrap(), 0.333333333);
+
+        // Only the final result is snapped, not the sum inside the product
+        let expr = Expr::mul(
+            Expr::add(Expr::number(0.1), Expr::number(0.2)),
+            Expr::number(1e17),
+        );
+        assert_eq!(expr.eval_with_config(&snap).unwrap(), 30000000000000004.0);
+    }
+
+    #[test]
+    fn test_flush_to_zero() {
+        let flush = EvalConfig {
+            flush_to_zero: Some(1e-12),
+            ..EvalConfig::default()
+        };
+        let tiny = Expr::div(Expr::number(-1.0), Expr::number(1e20));
+        assert_eq!(tiny.eval().unwrap(), -1e-20);
+        assert!(results_bitwise_equal(
+            tiny.eval_with_config(&flush).unwrap(),
+            0.0
+        ));
+        let half = Expr::div(Expr::number(1.0), Expr::number(2.0));
+        assert_eq!(half.eval_with_config(&flush).unwrap(), 0.5);
+
+        // Intermediate results are flushed too, and never underflow
+        let scaled = Expr::mul(tiny, Expr::number(1e30));
+        assert_eq!(scaled.eval_with_config(&flush).unwrap(), 0.0);
+        let expr = Expr::mul(Expr::number(1e-200), Expr::number(1e-200));
+        assert!(matches!(expr.eval(), Err(EvalError::Underflow { .. })));
+        assert_eq!(expr.eval_with_config(&flush).unwrap(), 0.0);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_eval_with_options() {
+        let options: EvalOptions = EvalConfig {
+            allow_overflow: true,
+            ..EvalConfig::default()
+        };
+        let big = Expr::mul(Expr::number(1e300), Expr::number(1e300));
+        assert_eq!(big.eval_with_options(&options).unwrap(), f64::INFINITY);
+    }
+
+    #[test]
+    fn test_div_by_zero_ieee() {
+        let ieee = EvalConfig {
+            div_by_zero: DivByZeroMode::Ieee,
+            ..EvalConfig::default()
+        };
+        let quotient = |a, b| Expr::div(Expr::number(a), Expr::number(b));
+        assert!(matches!(
+            quotient(1.0, 0.0).eval(),
+            Err(EvalError::DivisionByZero { .. })
+        ));
+        assert!(matches!(
+            quotient(1.0, 0.0).eval_with_config(&EvalConfig::default()),
+            Err(EvalError::DivisionByZero { .. })
+        ));
+        assert_eq!(
+            quotient(1.0, 0.0).eval_with_config(&ieee).unwrap(),
+            f64::INFINITY
+        );
+        assert_eq!(
+            quotient(-1.0, 0.0).eval_with_config(&ieee).unwrap(),
+            f64::NEG_INFINITY
+        );
+        assert!(quotient(0.0, 0.0).eval_with_config(&ieee).unwrap().is_nan());
+        assert_eq!(quotient(1.0, 4.0).eval_with_config(&ieee).unwrap(), 0.25);
+        // Only the quotient itself is exempt from the overflow check
+        let expr = Expr::add(quotient(1.0, 0.0), Expr::number(1.0));
+        assert!(matches!(
+            expr.eval_with_config(&ieee),
+            Err(EvalError::Overflow { .. })
+        ));
+        let permissive = EvalConfig {
+            allow_overflow: true,
+            ..ieee
+        };
+        assert_eq!(expr.eval_with_config(&permissive).unwrap(), f64::INFINITY);
+    }
+
+    #[test]
+    fn test_allow_overflow() {
+        let allow = EvalConfig {
+            allow_overflow: true,
+            ..EvalConfig::default()
+        };
+        let big = Expr::mul(Expr::number(1e300), Expr::number(1e300));
+        assert!(matches!(big.eval(), Err(EvalError::Overflow { .. })));
+        assert_eq!(big.eval_with_config(&allow).unwrap(), f64::INFINITY);
+        let literal = crate::parser::Parser::new("1e400")
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(matches!(literal.eval(), Err(EvalError::Overflow { .. })));
+        assert_eq!(literal.eval_with_config(&allow).unwrap(), f64::INFINITY);
+        let small = Expr::mul(Expr::number(1e-200), Expr::number(-1e-200));
+        assert!(matches!(small.eval(), Err(EvalError::Underflow { .. })));
+        assert!(results_bitwise_equal(
+            small.eval_with_config(&allow).unwrap(),
+            -0.0
+        ));
+        // Infinities that meet can still make NaN
+        let expr = Expr::sub(big.clone(), big);
+        assert!(matches!(
+            expr.eval_with_config(&allow),
+            Err(EvalError::NotANumber)
+        ));
+    }
+
+    #[test]
+    fn test_trig_degrees() {
+        let degrees = EvalConfig {
+            trig_degrees: true,
+            ..EvalConfig::default()
+        };
+        let call = |name, arg| Expr::call(name, vec![Expr::number(arg)]);
+        assert_eq!(call("sin", 90.0).eval_with_config(&degrees).unwrap(), 1.0);
+        assert_eq!(call("cos", 0.0).eval_with_config(&degrees).unwrap(), 1.0);
+        let tan = call("tan", 45.0).eval_with_config(&degrees).unwrap();
+        assert!((tan - 1.0).abs() < 1e-15);
+        assert_eq!(call("sin", 90.0).eval().unwrap(), 90f64.sin());
+        assert_eq!(call("asin", 1.0).eval_with_config(&degrees).unwrap(), 90.0);
+        assert_eq!(
+            call("acos", -1.0).eval_with_config(&degrees).unwrap(),
+            180.0
+        );
+        // Other functions are unaffected
+        assert_eq!(call("sqrt", 81.0).eval_with_config(&degrees).unwrap(), 9.0);
+        // Domain errors name the argument in degrees, as written
+        match call("tan", 90.0).eval_with_config(&degrees) {
+            Err(EvalError::DomainError(message)) => {
+                assert_eq!(message, "tan of 90, which is at a pole")
+            }
+            other => panic!("tan(90) gave {:?}", other),
+        }
+        assert!(matches!(
+            call("asin", 2.0).eval_with_config(&degrees),
+            Err(EvalError::DomainError(message)) if message == "asin of 2, which is outside [-1, 1]"
+        ));
+    }
+
+    #[test]
+    fn test_operator_overrides() {
+        let mut config = EvalConfig::default();
+        config.overrides.insert(BinOp::Add, |_, _| Ok(0.0));
+
+        let sum = Expr::add(Expr::number(2.0), Expr::number(3.0));
+        assert_eq!(sum.eval_with_config(&config).unwrap(), 0.0);
+        let product = Expr::mul(Expr::number(2.0), Expr::number(3.0));
+        assert_eq!(product.eval_with_config(&config).unwrap(), 6.0);
+
+        // A zero from an override is not an underflow, even for an
+        // operator whose built-in version never gives zero here
+        config.overrides.insert(BinOp::Mul, |_, _| Ok(0.0));
+        assert_eq!(product.eval_with_config(&config).unwrap(), 0.0);
+        config.overrides.insert(BinOp::Mul, |_, _| Ok(1e-310));
+        assert!(matches!(
+            product.eval_with_config(&config),
+            Err(EvalError::Underflow { op: None, .. })
+        ));
+
+        // An override takes over the built-in checks of its operator
+        config
+            .overrides
+            .insert(BinOp::Div, |a, b| Ok(if b == 0.0 { 0.0 } else { a / b }));
+        let quotient = Expr::div(Expr::number(1.0), Expr::number(0.0));
+        assert_eq!(quotient.eval_with_config(&config).unwrap(), 0.0);
+
+        config
+            .overrides
+            .insert(BinOp::Sub, |_, _| Err(EvalError::NotANumber));
+        let difference = Expr::sub(Expr::number(2.0), Expr::number(3.0));
+        assert!(matches!(
+            difference.eval_with_config(&config),
+            Err(EvalError::NotANumber)
+        ));
+    }
+
+    #[test]
+    fn test_strict_pow() {
+        let strict = EvalConfig {
+            strict_pow: true,
+            ..EvalConfig::default()
+        };
+        let cases = [
+            (0.0, 0.0, "0 raised to the power 0"),
+            (0.0, -1.0, "0 raised to a negative power"),
+            (-8.0, 1.0 / 3.0, "negative base with non-integer exponent"),
+        ];
+        for (base, exponent, message) in cases {
+            let expr = Expr::pow(Expr::number(base), Expr::number(exponent));
+            match expr.eval_with_config(&strict) {
+                Err(EvalError::DomainError(m)) => assert_eq!(m, message),
+                other => panic!("{} ^ {}: {:?}", base, exponent, other),
+            }
+        }
+
+        // The default keeps powf's conventions
+        let expr = Expr::pow(Expr::number(0.0), Expr::number(0.0));
+        assert_eq!(expr.eval().unwrap(), 1.0);
+        let expr = Expr::pow(Expr::number(-8.0), Expr::number(1.0 / 3.0));
+        assert!(matches!(expr.eval(), Err(EvalError::NotANumber)));
+
+        // Ordinary powers are unaffected by strict mode
+        let expr = Expr::pow(Expr::number(-2.0), Expr::number(3.0));
+        assert_eq!(expr.eval_with_config(&strict).unwrap(), -8.0);
+        let expr = Expr::pow(Expr::number(0.0), Expr::number(2.0));
+        assert_eq!(expr.eval_with_config(&strict).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_eval_tree() {
+        // 2 + 3 * 4
+        let expr = Expr::add(
+            Expr::number(2.0),
+            Expr::mul(Expr::number(3.0), Expr::number(4.0)),
+        );
+        let root = expr.eval_tree();
+        assert_eq!(root.expr_string, "2 + 3 * 4");
+        assert_eq!(root.value.unwrap(), 14.0);
+        assert_eq!(root.children.len(), 2);
+        assert_eq!(root.children[0].value.clone().unwrap(), 2.0);
+
+        let mul = &root.children[1];
+        assert_eq!(mul.expr_string, "3 * 4");
+        assert_eq!(mul.value.clone().unwrap(), 12.0);
+        assert_eq!(mul.children.len(), 2);
+    }
+
+    #[test]
+    fn test_eval_tree_error() {
+        // (1 / 0) + 2
+        let expr = Expr::add(
+            Expr::div(Expr::number(1.0), Expr::number(0.0)),
+            Expr::number(2.0),
+        );
+        let root = expr.eval_tree();
+        assert!(matches!(root.value, Err(EvalError::DivisionByZero { .. })));
+        assert!(matches!(
+            root.children[0].value,
+            Err(EvalError::DivisionByZero { .. })
+        ));
+        assert_eq!(root.children[1].value.clone().unwrap(), 2.0);
+    }
+
+    #[test]
+    fn test_as_number() {
+        assert_eq!(Expr::number(3.0).as_number(), Some(3.0));
+        assert_eq!(
+            Expr::add(Expr::number(1.0), Expr::number(2.0)).as_number(),
+            None
+        );
+        assert_eq!(Expr::neg(Expr::number(3.0)).as_number(), None);
+    }
+
+    #[test]
+    fn test_eval_rounded() {
+        let third = Expr::div(Expr::number(1.0), Expr::number(3.0));
+        assert_eq!(third.eval_rounded(4).unwrap(), (0.3333, true));
+        assert_eq!(Expr::number(2.0).eval_rounded(4).unwrap(), (2.0, false));
+        assert_eq!(
+            Expr::number(123456.0).eval_rounded(2).unwrap(),
+            (120000.0, true)
+        );
+        assert_eq!(
+            Expr::number(-0.0012345).eval_rounded(3).unwrap(),
+            (-0.00123, true)
+        );
+    }
+
+    #[test]
+    fn test_children() {
+        let two = Expr::number(2.0);
+        let three = Expr::number(3.0);
+        let sum = Expr::add(two.clone(), three.clone());
+        assert_eq!(sum.children(), [&two, &three]);
+
+        assert_eq!(Expr::neg(two.clone()).children(), [&two]);
+        assert!(Expr::number(5.0).children().is_empty());
+    }
+
+    #[test]
+    fn test_maybe_expr() {
+        let absent = MaybeExpr(None);
+        assert_eq!(absent.eval_or_zero().unwrap(), 0.0);
+        assert_eq!(absent.eval_or(7.0).unwrap(), 7.0);
+
+        let present = MaybeExpr::from(Some(Expr::number(2.0)));
+        assert_eq!(present.eval_or(7.0).unwrap(), 2.0);
+
+        let failing = MaybeExpr(Some(Expr::div(Expr::number(1.0), Expr::number(0.0))));
+        assert!(failing.eval_or_zero().is_err());
+    }
+
+    #[test]
+    fn test_results_bitwise_equal() {
+        assert!(results_bitwise_equal(0.1 + 0.2, 0.1 + 0.2));
+        assert!(results_bitwise_equal(f64::NAN, f64::NAN));
+        assert!(!results_bitwise_equal(0.0, -0.0));
+    }
+
+    #[test]
+    fn test_eval_deterministic() {
+        // Mixes operations whose rounding depends on evaluation order
+        let terms: Vec<Expr> = (1..=50)
+            .map(|i| Expr::div(Expr::number(1.0), Expr::number(i as f64 * 0.7)))
+            .collect();
+        let expr = Expr::mul(
+            Expr::balanced_sum(terms),
+            Expr::pow(Expr::number(1.1), Expr::number(0.3)),
+        );
+
+        let first = expr.eval().unwrap();
+        for _ in 0..10 {
+            assert!(results_bitwise_equal(expr.eval().unwrap(), first));
+        }
+        let canonical = expr.canonicalize();
+        assert!(results_bitwise_equal(
+            canonical.eval().unwrap(),
+            canonical.canonicalize().eval().unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_collect_constants() {
+        // 2 + 3 * 4 - 5
+        let expr = Expr::sub(
+            Expr::add(
+                Expr::number(2.0),
+                Expr::mul(Expr::number(3.0), Expr::number(4.0)),
+            ),
+            Expr::number(5.0),
+        );
+        assert_eq!(expr.collect_constants(), [2.0, 3.0, 4.0, 5.0]);
+    }
+
+    #[test]
+    fn test_default() {
+        assert_eq!(Expr::default(), Expr::Number(0.0));
+        assert_eq!(Expr::default().eval().unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_eval_with_cancel() {
+        let expr = Expr::balanced_sum(vec![Expr::number(1.0); 100]);
+
+        let cancel = AtomicBool::new(false);
+        assert_eq!(expr.eval_with_cancel(&cancel).unwrap(), 100.0);
+
+        cancel.store(true, Ordering::Relaxed);
+        assert!(matches!(
+            expr.eval_with_cancel(&cancel),
+            Err(EvalError::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn test_non_finite_literals() {
+        let expr = Expr::add(Expr::number(f64::NAN), Expr::number(1.0));
+        assert!(matches!(expr.eval(), Err(EvalError::NotANumber)));
+
+        let expr = Expr::sub(Expr::number(1.0), Expr::number(f64::INFINITY));
+        assert!(matches!(expr.eval(), Err(EvalError::Overflow { .. })));
+    }
+
     #[test]
     fn test_division_by_zero() {
         let expr = Expr::div(Expr::number(1.0), Expr::number(0.0));
         let result = expr.eval();
-        assert!(matches!(result, Err(EvalError::DivisionByZero)));
+        assert!(matches!(
+            result,
+            Err(EvalError::DivisionByZero {
+                kind: DivisorKind::Literal,
+                dividend: 1.0
+            })
+        ));
+
+        // 1 / (2 - 2)
+        let expr = Expr::div(
+            Expr::number(1.0),
+            Expr::sub(Expr::number(2.0), Expr::number(2.0)),
+        );
+        let result = expr.eval();
+        assert!(matches!(
+            result,
+            Err(EvalError::DivisionByZero {
+                kind: DivisorKind::Computed,
+                ..
+            })
+        ));
     }
 
     #[test]
     fn test_overflow() {
         let expr = Expr::mul(Expr::number(f64::MAX), Expr::number(2.0));
         let result = expr.eval();
-        assert!(matches!(result, Err(EvalError::Overflow)));
+        match result {
+            Err(EvalError::Overflow { op, lhs, rhs }) => {
+                assert_eq!(op, Some(OpKind::Mul));
+                assert_eq!(lhs, f64::MAX);
+                assert_eq!(rhs, Some(2.0));
+            }
+            other => panic!("expected overflow, got {:?}", other),
+        }
+
+        let expr = Expr::pow(Expr::number(10.0), Expr::number(400.0));
+        let err = expr.eval().unwrap_err();
+        assert!(matches!(
+            err,
+            EvalError::Overflow {
+                op: Some(OpKind::Pow),
+                ..
+            }
+        ));
+        assert!(
+            err.to_string()
+                .starts_with("Numeric overflow in exponentiation")
+        );
     }
 
     #[test]
-    fn test_underflow() {
+    fn test_negative_overflow() {
         let expr = Expr::mul(Expr::number(f64::MIN), Expr::number(2.0));
-        let result = expr.eval();
-        assert!(matches!(result, Err(EvalError::Underflow)));
+        assert!(matches!(
+            expr.eval(),
+            Err(EvalError::Overflow {
+                op: Some(OpKind::Mul),
+                ..
+            })
+        ));
+        let expr = Expr::neg(Expr::pow(Expr::number(10.0), Expr::number(400.0)));
+        assert!(matches!(expr.eval(), Err(EvalError::Overflow { .. })));
+    }
+
+    #[test]
+    fn test_underflow() {
+        // A subnormal result
+        let expr = Expr::div(Expr::number(f64::MIN_POSITIVE), Expr::number(4.0));
+        match expr.eval() {
+            Err(EvalError::Underflow { op, lhs, rhs }) => {
+                assert_eq!(op, Some(OpKind::Div));
+                assert_eq!(lhs, f64::MIN_POSITIVE);
+                assert_eq!(rhs, Some(4.0));
+            }
+            other => panic!("expected underflow, got {:?}", other),
+        }
+
+        // Nonzero operands whose exact result rounds to zero
+        let expr = Expr::mul(Expr::number(1e-200), Expr::number(-1e-200));
+        assert!(matches!(expr.eval(), Err(EvalError::Underflow { .. })));
+        let expr = Expr::pow(Expr::number(0.5), Expr::number(2000.0));
+        let err = expr.eval().unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("Numeric underflow in exponentiation")
+        );
+
+        // Results that are exactly zero are fine
+        let zero = |expr: Expr| assert_eq!(expr.eval().unwrap(), 0.0);
+        zero(Expr::sub(Expr::number(1e-300), Expr::number(1e-300)));
+        zero(Expr::mul(Expr::number(0.0), Expr::number(1e-300)));
+        zero(Expr::pow(Expr::number(0.0), Expr::number(3.0)));
+        zero(Expr::call("ln", vec![Expr::number(1.0)]));
+
+        // Exact operations on or to subnormals are fine
+        let subnormal = |expr: Expr, value: f64| assert_eq!(expr.eval().unwrap(), value);
+        subnormal(Expr::number(5e-320), 5e-320);
+        subnormal(Expr::Neg(Box::new(Expr::number(5e-320))), -5e-320);
+        subnormal(Expr::add(Expr::number(5e-320), Expr::number(0.0)), 5e-320);
+        subnormal(Expr::mul(Expr::number(5e-320), Expr::number(1.0)), 5e-320);
+        let min = f64::MIN_POSITIVE;
+        subnormal(
+            Expr::sub(Expr::number(min * 1.5), Expr::number(min)),
+            min * 0.5,
+        );
+    }
+
+    #[test]
+    fn test_nan_result() {
+        let nan = |expr: Expr| assert!(matches!(expr.eval(), Err(EvalError::NotANumber)));
+        nan(Expr::pow(Expr::number(-8.0), Expr::number(0.5)));
+        // Functions reject arguments outside their domain before they can
+        // produce NaN
+        let expr = Expr::call("sqrt", vec![Expr::number(-1.0)]);
+        assert!(matches!(expr.eval(), Err(EvalError::DomainError(_))));
     }
 }
@@ -0,0 +1,56 @@
+use std::io::{self, BufRead, Write};
+
+use crate::parser::Parser;
+
+/// Evaluates each line of `reader` as an expression and writes either
+/// `input = result` or `input: <error>` to `writer`.
+///
+/// Blank lines are skipped and an invalid line does not stop the stream;
+/// only I/O errors do.
+pub fn eval_stream(reader: impl BufRead, mut writer: impl Write) -> io::Result<()> {
+    for line in reader.lines() {
+        let line = line?;
+        let input = line.trim();
+        if input.is_empty() {
+            continue;
+        }
+        writeln!(writer, "{}", eval_line(input))?;
+    }
+    Ok(())
+}
+
+fn eval_line(input: &str) -> String {
+    match Parser::new(input) {
+        Ok(mut parser) => match parser.parse() {
+            Ok(expr) => match expr.eval() {
+                Ok(value) => format!("{} = {}", input, value),
+                Err(e) => format!("{}: Evaluation error: {}", input, e),
+            },
+            Err(e) => format!("{}: {}", input, e),
+        },
+        Err(e) => format!("{}: {}", input, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eval_stream() {
+        let input: &[u8] = b"2 + 3\n\n10 / 0\n(1 + 2) * 4\n";
+        let mut output = Vec::new();
+        eval_stream(input, &mut output).expect("I/O failed");
+
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "2 + 3 = 5",
+                "10 / 0: Evaluation error: Division by zero: 10 / 0",
+                "(1 + 2) * 4 = 12",
+            ]
+        );
+    }
+}
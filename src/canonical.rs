@@ -0,0 +1,179 @@
+use std::cmp::Ordering;
+
+use crate::ast::Expr;
+
+impl Expr {
+    /// Returns a canonical form of the expression in which chains of the
+    /// commutative operators `+` and `*` are flattened and their operands
+    /// sorted, so that e.g. `b + a` and `a + b` produce equal trees.
+    ///
+    /// `-`, `/` and `^` keep their operand order. The result has the same
+    /// mathematical value as the input.
+    pub fn canonicalize(&self) -> Expr {
+        match self {
+            Expr::Number(n) => Expr::Number(*n),
+            Expr::Var(name) => Expr::Var(name.clone()),
+            Expr::Call(name, args) => {
+                Expr::call(name.clone(), args.iter().map(Expr::canonicalize).collect())
+            }
+            Expr::Add(..) => Self::canonical_chain(self, Expr::add),
+            Expr::Mul(..) => Self::canonical_chain(self, Expr::mul),
+            Expr::Sub(a, b) => Expr::sub(a.canonicalize(), b.canonicalize()),
+            Expr::Div(a, b) => Expr::div(a.canonicalize(), b.canonicalize()),
+            Expr::Pow(a, b) => Expr::pow(a.canonicalize(), b.canonicalize()),
+            Expr::Range(a, b) => Expr::range(a.canonicalize(), b.canonicalize()),
+            Expr::Neg(a) => Expr::neg(a.canonicalize()),
+            Expr::DoubleFactorial(a) => Expr::double_factorial(a.canonicalize()),
+            Expr::Let { name, value, body } => {
+                Expr::let_in(name.clone(), value.canonicalize(), body.canonicalize())
+            }
+        }
+    }
+
+    /// Returns whether the two trees are equal up to reordering and
+    /// regrouping of `+` and `*` operands, by comparing their canonical
+    /// forms.
+    pub fn semantically_eq(&self, other: &Expr) -> bool {
+        self.canonicalize() == other.canonicalize()
+    }
+
+    fn canonical_chain(&self, combine: fn(Expr, Expr) -> Expr) -> Expr {
+        let mut operands = Vec::new();
+        self.collect_chain(std::mem::discriminant(self), &mut operands);
+
+        let mut operands: Vec<Expr> = operands.into_iter().map(Expr::canonicalize).collect();
+        operands.sort_by(Expr::structural_cmp);
+
+        let mut operands = operands.into_iter();
+        let first = operands.next().expect("a chain has at least two operands");
+        operands.fold(first, combine)
+    }
+
+    fn collect_chain<'e>(&'e self, op: std::mem::Discriminant<Expr>, operands: &mut Vec<&'e Expr>) {
+        match self {
+            Expr::Add(a, b) | Expr::Mul(a, b) if std::mem::discriminant(self) == op => {
+                a.collect_chain(op, operands);
+                b.collect_chain(op, operands);
+            }
+            _ => operands.push(self),
+        }
+    }
+
+    /// A total order over trees: by node kind first, then by numeric value
+    /// or operands.
+    fn structural_cmp(&self, other: &Expr) -> Ordering {
+        fn rank(expr: &Expr) -> u8 {
+            match expr {
+                Expr::Number(_) => 0,
+                Expr::Neg(_) => 1,
+                Expr::Add(..) => 2,
+                Expr::Sub(..) => 3,
+                Expr::Mul(..) => 4,
+                Expr::Div(..) => 5,
+                Expr::Pow(..) => 6,
+                Expr::Range(..) => 7,
+                Expr::Var(_) => 8,
+                Expr::Call(..) => 9,
+                Expr::Let { .. } => 10,
+                Expr::DoubleFactorial(_) => 11,
+            }
+        }
+
+        match (self, other) {
+            (Expr::Number(a), Expr::Number(b)) => a.total_cmp(b),
+            (Expr::Var(a), Expr::Var(b)) => a.cmp(b),
+            (Expr::Call(n1, a1), Expr::Call(n2, a2)) => n1.cmp(n2).then_with(|| {
+                a1.iter()
+                    .zip(a2)
+                    .map(|(x, y)| x.structural_cmp(y))
+                    .find(|ordering| ordering.is_ne())
+                    .unwrap_or_else(|| a1.len().cmp(&a2.len()))
+            }),
+            (Expr::Neg(a), Expr::Neg(b)) | (Expr::DoubleFactorial(a), Expr::DoubleFactorial(b)) => {
+                a.structural_cmp(b)
+            }
+            (
+                Expr::Let {
+                    name: n1,
+                    value: v1,
+                    body: b1,
+                },
+                Expr::Let {
+                    name: n2,
+                    value: v2,
+                    body: b2,
+                },
+            ) => n1
+                .cmp(n2)
+                .then_with(|| v1.structural_cmp(v2))
+                .then_with(|| b1.structural_cmp(b2)),
+            (Expr::Add(a1, b1), Expr::Add(a2, b2))
+            | (Expr::Sub(a1, b1), Expr::Sub(a2, b2))
+            | (Expr::Mul(a1, b1), Expr::Mul(a2, b2))
+            | (Expr::Div(a1, b1), Expr::Div(a2, b2))
+            | (Expr::Pow(a1, b1), Expr::Pow(a2, b2))
+            | (Expr::Range(a1, b1), Expr::Range(a2, b2)) => {
+                a1.structural_cmp(a2).then_with(|| b1.structural_cmp(b2))
+            }
+            _ => rank(self).cmp(&rank(other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn n(value: f64) -> Expr {
+        Expr::number(value)
+    }
+
+    #[test]
+    fn test_canonicalize_commutative() {
+        let a = Expr::sub(n(1.0), n(2.0));
+        let b = n(3.0);
+        assert_eq!(
+            Expr::add(a.clone(), b.clone()).canonicalize(),
+            Expr::add(b.clone(), a.clone()).canonicalize()
+        );
+        assert_eq!(
+            Expr::mul(a.clone(), b.clone()).canonicalize(),
+            Expr::mul(b.clone(), a.clone()).canonicalize()
+        );
+    }
+
+    #[test]
+    fn test_canonicalize_non_commutative() {
+        assert_ne!(
+            Expr::sub(n(1.0), n(2.0)).canonicalize(),
+            Expr::sub(n(2.0), n(1.0)).canonicalize()
+        );
+        assert_ne!(
+            Expr::div(n(1.0), n(2.0)).canonicalize(),
+            Expr::div(n(2.0), n(1.0)).canonicalize()
+        );
+    }
+
+    #[test]
+    fn test_canonicalize_flattens_chains() {
+        // (1 + 2) + 3, 3 + (2 + 1)
+        let left = Expr::add(Expr::add(n(1.0), n(2.0)), n(3.0));
+        let right = Expr::add(n(3.0), Expr::add(n(2.0), n(1.0)));
+        assert_eq!(left.canonicalize(), right.canonicalize());
+        assert_eq!(right.canonicalize().eval().unwrap(), right.eval().unwrap());
+    }
+
+    #[test]
+    fn test_semantically_eq() {
+        let a = Expr::neg(n(1.0));
+        let b = Expr::div(n(2.0), n(3.0));
+        let c = n(4.0);
+        assert!(Expr::add(a.clone(), b.clone()).semantically_eq(&Expr::add(b.clone(), a.clone())));
+        assert!(
+            Expr::add(Expr::add(a.clone(), b.clone()), c.clone())
+                .semantically_eq(&Expr::add(a.clone(), Expr::add(b.clone(), c.clone())))
+        );
+        assert!(!Expr::sub(a.clone(), b.clone()).semantically_eq(&Expr::sub(b.clone(), a.clone())));
+        assert!(!Expr::add(a.clone(), b.clone()).semantically_eq(&Expr::mul(a, b)));
+    }
+}
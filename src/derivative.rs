@@ -0,0 +1,132 @@
+use crate::ast::Expr;
+
+impl Expr {
+    /// Differentiates the expression with respect to the variable `var`,
+    /// using the sum, difference, product and quotient rules, the chain
+    /// rule through `-` and the power rule for exponents that do not
+    /// mention `var`. The result is not simplified; run
+    /// [`Expr::simplify`] on it to make it readable, so the derivative of
+    /// `x ^ 2` becomes `2 * x`.
+    ///
+    /// A subexpression that does not mention `var` differentiates to `0`.
+    /// Other forms that mention it, such as `2 ^ x`, function calls and
+    /// `let`s, are left as a call `derivative(f, x)`. There is no such
+    /// function, so evaluating the result always fails, but not always
+    /// with [`EvalError::UnknownFunction`](crate::error::EvalError::UnknownFunction):
+    /// the call's arguments are evaluated first, so an unbound variable in
+    /// `f`, or an error anywhere evaluated before the call, is reported
+    /// instead. Check for the call's name in the result to detect a
+    /// derivative that was not found.
+    pub fn derivative(&self, var: &str) -> Expr {
+        if !self.mentions(var) {
+            return Expr::Number(0.0);
+        }
+        match self {
+            Expr::Var(_) => Expr::Number(1.0),
+            Expr::Add(a, b) => Expr::add(a.derivative(var), b.derivative(var)),
+            Expr::Sub(a, b) => Expr::sub(a.derivative(var), b.derivative(var)),
+            Expr::Mul(a, b) => Expr::add(
+                Expr::mul(a.derivative(var), b.as_ref().clone()),
+                Expr::mul(a.as_ref().clone(), b.derivative(var)),
+            ),
+            Expr::Div(a, b) => Expr::div(
+                Expr::sub(
+                    Expr::mul(a.derivative(var), b.as_ref().clone()),
+                    Expr::mul(a.as_ref().clone(), b.derivative(var)),
+                ),
+                Expr::pow(b.as_ref().clone(), Expr::Number(2.0)),
+            ),
+            Expr::Neg(a) => Expr::neg(a.derivative(var)),
+            Expr::Pow(a, b) if !b.mentions(var) => Expr::mul(
+                Expr::mul(
+                    b.as_ref().clone(),
+                    Expr::pow(
+                        a.as_ref().clone(),
+                        Expr::sub(b.as_ref().clone(), Expr::Number(1.0)),
+                    ),
+                ),
+                a.derivative(var),
+            ),
+            _ => Expr::call("derivative", vec![self.clone(), Expr::var(var)]),
+        }
+    }
+
+    /// Whether `var` occurs free in the expression, i.e. not only inside
+    /// the body of a `let` that rebinds it.
+    fn mentions(&self, var: &str) -> bool {
+        match self {
+            Expr::Var(name) => name == var,
+            Expr::Let { name, value, body } => {
+                value.mentions(var) || (name != var && body.mentions(var))
+            }
+            _ => self.children().into_iter().any(|child| child.mentions(var)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::EvalError;
+    use crate::parser::Parser;
+    use std::collections::HashMap;
+
+    fn parse(input: &str) -> Expr {
+        Parser::new(input).unwrap().parse().unwrap()
+    }
+
+    fn derived(input: &str) -> String {
+        parse(input).derivative("x").simplify().to_string()
+    }
+
+    /// The derivative with respect to `x`, evaluated at `x`.
+    fn slope(input: &str, x: f64) -> f64 {
+        let env = HashMap::from([("x".to_string(), x)]);
+        parse(input).derivative("x").eval_with(&env).unwrap()
+    }
+
+    #[test]
+    fn test_derivative_polynomial() {
+        assert_eq!(derived("7"), "0");
+        assert_eq!(derived("x"), "1");
+        assert_eq!(derived("y"), "0");
+        assert_eq!(derived("x * x"), "x + x");
+        assert_eq!(derived("x ^ 2"), "2 * x");
+        assert_eq!(derived("x ^ 3"), "3 * x ^ 2");
+        assert_eq!(derived("3 * x ^ 2 + 2 * x - 1"), "3 * (2 * x) + 2");
+        assert_eq!(slope("3 * x ^ 2 + 2 * x - 1", 5.0), 32.0);
+        assert_eq!(derived("-(x * y)"), "-y");
+    }
+
+    #[test]
+    fn test_derivative_quotient() {
+        assert_eq!(derived("1 / x"), "-1 / x ^ 2");
+        assert_eq!(slope("1 / x", 2.0), -0.25);
+        // (x + 1) / (x - 1) has derivative -2 / (x - 1) ^ 2
+        assert_eq!(slope("(x + 1) / (x - 1)", 3.0), -0.5);
+    }
+
+    #[test]
+    fn test_derivative_composed() {
+        // The chain rule through a power: 3 * (x ^ 2 + 1) ^ 2 * 2x
+        assert_eq!(derived("(x ^ 2 + 1) ^ 3"), "3 * (x ^ 2 + 1) ^ 2 * (2 * x)");
+        assert_eq!(slope("(x ^ 2 + 1) ^ 3", 1.0), 24.0);
+        assert_eq!(slope("-(x * (x - 2)) / 4", 3.0), -1.0);
+        // A let that rebinds x hides it from the body
+        assert_eq!(derived("let x = 2 in x * x"), "0");
+    }
+
+    #[test]
+    fn test_derivative_unsupported() {
+        assert_eq!(derived("x ^ y"), "y * x ^ (y - 1)");
+        assert_eq!(derived("2 ^ x"), "derivative(2 ^ x, x)");
+        assert_eq!(derived("sqrt(x) + x"), "derivative(sqrt(x), x) + 1");
+        assert_eq!(derived("2 ^ y + sqrt(y)"), "0");
+        let env = HashMap::from([("x".to_string(), 2.0)]);
+        let result = parse("x ^ x").derivative("x").eval_with(&env);
+        assert!(matches!(result, Err(EvalError::UnknownFunction(name)) if name == "derivative"));
+        // The fallback's arguments are evaluated before the call fails
+        let result = parse("sqrt(x * y)").derivative("x").eval_with(&env);
+        assert!(matches!(result, Err(EvalError::UndefinedVariable(name)) if name == "y"));
+    }
+}
@@ -0,0 +1,93 @@
+use std::fmt;
+
+use crate::error::ParseError;
+
+/// A parse error bundled with the source it came from, ready to be printed
+/// rustc-style with the offending line and a caret under the error column,
+/// or carets under the whole span when the error has a single-line one.
+///
+/// Lexer errors convert into `ParseError`, so both can be wrapped.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub error: ParseError,
+    pub source: String,
+}
+
+impl Diagnostic {
+    pub fn new(error: impl Into<ParseError>, source: impl Into<String>) -> Self {
+        Diagnostic {
+            error: error.into(),
+            source: source.into(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let location = self.error.location;
+        let line = self.source.lines().nth(location.line - 1).unwrap_or("");
+        let gutter = " ".repeat(location.line.to_string().len());
+
+        writeln!(f, "error: {}", self.error.message)?;
+        writeln!(f, "{}--> {}", gutter, location)?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", location.line, line)?;
+        let width = match self.error.span() {
+            Some(span)
+                if span.start.line == span.end.line && span.end.column >= location.column =>
+            {
+                span.end.column - location.column + 1
+            }
+            _ => 1,
+        };
+        write!(
+            f,
+            "{} | {}{}",
+            gutter,
+            " ".repeat(location.column.saturating_sub(1)),
+            "^".repeat(width)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_diagnostic_display() {
+        let source = "(2 + 3";
+        let err = Parser::new(source).unwrap().parse().unwrap_err();
+        let rendered = Diagnostic::new(err, source).to_string();
+        assert_eq!(
+            rendered,
+            "error: Expected RightParen, got Eof\n\
+             \x20--> line 1, column 7\n\
+             \x20 |\n\
+             1 | (2 + 3\n\
+             \x20 |       ^"
+        );
+    }
+
+    #[test]
+    fn test_diagnostic_multiline_lexer_error() {
+        let source = "1 +\n2 @ 3";
+        let err = Parser::new(source).unwrap().parse().unwrap_err();
+        let rendered = Diagnostic::new(err, source).to_string();
+        assert!(rendered.contains("2 | 2 @ 3"));
+        assert!(rendered.ends_with("  |   ^"));
+    }
+
+    #[test]
+    fn test_diagnostic_span() {
+        let source = "1 + 12.";
+        let err = Parser::new(source).unwrap().parse().unwrap_err();
+        let rendered = Diagnostic::new(err, source).to_string();
+        assert!(
+            rendered.ends_with("1 | 1 + 12.\n  |     ^^^"),
+            "{}",
+            rendered
+        );
+    }
+}
@@ -1,7 +1,9 @@
 use std::fmt;
 
 /// Represents a location in the source code.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///
+/// Locations order by line, then by column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Location {
     pub line: usize,
     pub column: usize,
@@ -11,6 +13,16 @@ impl Location {
     pub fn new(line: usize, column: usize) -> Self {
         Location { line, column }
     }
+
+    /// Returns the location `n` columns further along the same line.
+    pub fn advance_column(self, n: usize) -> Self {
+        Location::new(self.line, self.column + n)
+    }
+
+    /// Returns the first column of the following line.
+    pub fn next_line(self) -> Self {
+        Location::new(self.line + 1, 1)
+    }
 }
 
 impl fmt::Display for Location {
@@ -19,11 +31,27 @@ impl fmt::Display for Location {
     }
 }
 
+/// A range of source text from `start` to `end`, both inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: Location,
+    pub end: Location,
+}
+
+impl Span {
+    pub fn new(start: Location, end: Location) -> Self {
+        Span { start, end }
+    }
+}
+
 /// Error that occurs during lexical analysis.
 #[derive(Debug, Clone)]
 pub struct LexerError {
     pub message: String,
     pub location: Location,
+    /// The extent of the offending text, such as a whole malformed number
+    /// literal, when it is longer than the character at `location`.
+    pub span: Option<Span>,
 }
 
 impl LexerError {
@@ -31,8 +59,14 @@ impl LexerError {
         LexerError {
             message: message.into(),
             location,
+            span: None,
         }
     }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
 }
 
 impl fmt::Display for LexerError {
@@ -48,6 +82,8 @@ impl std::error::Error for LexerError {}
 pub struct ParseError {
     pub message: String,
     pub location: Location,
+    /// The lexer error this was converted from, reported by `source()`.
+    cause: Option<LexerError>,
 }
 
 impl ParseError {
@@ -55,15 +91,22 @@ impl ParseError {
         ParseError {
             message: message.into(),
             location,
+            cause: None,
         }
     }
 
     pub fn from_lexer_error(err: LexerError) -> Self {
         ParseError {
-            message: err.message,
+            message: err.message.clone(),
             location: err.location,
+            cause: Some(err),
         }
     }
+
+    /// The span of the lexer error this was converted from, if it has one.
+    pub fn span(&self) -> Option<Span> {
+        self.cause.as_ref().and_then(|err| err.span)
+    }
 }
 
 impl fmt::Display for ParseError {
@@ -72,7 +115,25 @@ impl fmt::Display for ParseError {
     }
 }
 
-impl std::error::Error for ParseError {}
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.cause
+            .as_ref()
+            .map(|err| err as &(dyn std::error::Error + 'static))
+    }
+}
+
+impl From<LexerError> for std::io::Error {
+    fn from(err: LexerError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, err.to_string())
+    }
+}
+
+impl From<ParseError> for std::io::Error {
+    fn from(err: ParseError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, err.to_string())
+    }
+}
 
 impl From<LexerError> for ParseError {
     fn from(err: LexerError) -> Self {
@@ -80,22 +141,347 @@ impl From<LexerError> for ParseError {
     }
 }
 
+/// Where the zero divisor of a failed division came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DivisorKind {
+    /// The divisor was written as a zero literal, as in `1 / 0`.
+    Literal,
+    /// The divisor evaluated to zero, as in `1 / (2 - 2)`.
+    Computed,
+}
+
+/// The arithmetic operation an overflow or underflow happened in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpKind {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    Neg,
+    /// A built-in function call such as `exp(1000)`.
+    Call,
+    DoubleFactorial,
+}
+
+impl fmt::Display for OpKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            OpKind::Add => "addition",
+            OpKind::Sub => "subtraction",
+            OpKind::Mul => "multiplication",
+            OpKind::Div => "division",
+            OpKind::Pow => "exponentiation",
+            OpKind::Neg => "negation",
+            OpKind::Call => "function call",
+            OpKind::DoubleFactorial => "double factorial",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 /// Error that occurs during expression evaluation.
+///
+/// Arithmetic errors carry the operand values of the failing operation;
+/// `rhs` is `None` for unary operations. `op` is the operation that
+/// overflowed or underflowed, or `None` when an infinite value came from a
+/// literal or variable rather than from arithmetic.
 #[derive(Debug, Clone)]
 pub enum EvalError {
-    DivisionByZero,
-    Overflow,
-    Underflow,
+    DivisionByZero {
+        kind: DivisorKind,
+        dividend: f64,
+    },
+    /// A result too large in magnitude for an `f64`, of either sign.
+    Overflow {
+        op: Option<OpKind>,
+        lhs: f64,
+        rhs: Option<f64>,
+    },
+    /// A nonzero result too small in magnitude to be represented at full
+    /// precision: either subnormal, or rounded all the way to zero. Exact
+    /// results, such as the negation of a subnormal, are not underflows.
+    Underflow {
+        op: Option<OpKind>,
+        lhs: f64,
+        rhs: Option<f64>,
+    },
+    NotANumber,
+    NonIntegerResult,
+    /// An integer literal of 2^53 or more in magnitude, which an `f64` may have
+    /// rounded, where exact integer arithmetic was required.
+    ImpreciseLiteral(f64),
+    Cancelled,
+    /// An operation was applied outside its mathematical domain.
+    DomainError(String),
+    /// A range such as `0..10` was evaluated where a single number is
+    /// expected.
+    NotAScalar,
+    UndefinedVariable(String),
+    UnknownFunction(String),
+    ArityMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
 }
 
 impl fmt::Display for EvalError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            EvalError::DivisionByZero => write!(f, "Division by zero"),
-            EvalError::Overflow => write!(f, "Numeric overflow"),
-            EvalError::Underflow => write!(f, "Numeric underflow"),
+            EvalError::DivisionByZero { dividend, .. } => {
+                write!(f, "Division by zero: {} / 0", Operand(*dividend))
+            }
+            EvalError::Overflow { op, lhs, rhs } => {
+                write!(f, "Numeric overflow")?;
+                write_operation(f, *op, *lhs, *rhs)
+            }
+            EvalError::Underflow { op, lhs, rhs } => {
+                write!(f, "Numeric underflow")?;
+                write_operation(f, *op, *lhs, *rhs)
+            }
+            EvalError::NotANumber => write!(f, "Result is not a number"),
+            EvalError::NonIntegerResult => write!(f, "Result is not an integer"),
+            EvalError::ImpreciseLiteral(n) => {
+                write!(
+                    f,
+                    "Literal {} may not be exact; integers from 2^53 up may be rounded",
+                    n
+                )
+            }
+            EvalError::Cancelled => write!(f, "Evaluation cancelled"),
+            EvalError::DomainError(message) => write!(f, "Domain error: {}", message),
+            EvalError::NotAScalar => write!(f, "A range is not a single number"),
+            EvalError::UndefinedVariable(name) => write!(f, "Undefined variable: {}", name),
+            EvalError::UnknownFunction(name) => write!(f, "Unknown function: {}", name),
+            EvalError::ArityMismatch {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Function {} takes {} argument{}, got {}",
+                name,
+                expected,
+                if *expected == 1 { "" } else { "s" },
+                found
+            ),
         }
     }
 }
 
 impl std::error::Error for EvalError {}
+
+/// Any error from turning a string into a value: lexing, parsing or
+/// evaluating it.
+#[derive(Debug, Clone)]
+pub enum ExprError {
+    Lexer(LexerError),
+    Parse(ParseError),
+    Eval(EvalError),
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExprError::Lexer(err) => err.fmt(f),
+            ExprError::Parse(err) => err.fmt(f),
+            ExprError::Eval(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ExprError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ExprError::Lexer(err) => Some(err),
+            ExprError::Parse(err) => Some(err),
+            ExprError::Eval(err) => Some(err),
+        }
+    }
+}
+
+impl From<LexerError> for ExprError {
+    fn from(err: LexerError) -> Self {
+        ExprError::Lexer(err)
+    }
+}
+
+/// A parse error that wraps a lexer error, as when the input goes bad
+/// after its first token, converts back to that lexer error.
+impl From<ParseError> for ExprError {
+    fn from(err: ParseError) -> Self {
+        match err.cause {
+            Some(cause) => ExprError::Lexer(cause),
+            None => ExprError::Parse(err),
+        }
+    }
+}
+
+impl From<EvalError> for ExprError {
+    fn from(err: EvalError) -> Self {
+        ExprError::Eval(err)
+    }
+}
+
+impl From<ExprError> for std::io::Error {
+    fn from(err: ExprError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, err.to_string())
+    }
+}
+
+/// Displays an operand value compactly, switching to scientific notation
+/// for very large or very small magnitudes.
+struct Operand(f64);
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let magnitude = self.0.abs();
+        if magnitude != 0.0 && !(1e-6..1e16).contains(&magnitude) {
+            write!(f, "{:e}", self.0)
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+fn write_operation(
+    f: &mut fmt::Formatter<'_>,
+    op: Option<OpKind>,
+    lhs: f64,
+    rhs: Option<f64>,
+) -> fmt::Result {
+    if let Some(op) = op {
+        write!(f, " in {}", op)?;
+    }
+    match rhs {
+        Some(rhs) => write!(f, " (operands {} and {})", Operand(lhs), Operand(rhs)),
+        None => write!(f, " (operand {})", Operand(lhs)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_location_ordering() {
+        assert!(Location::new(1, 5) < Location::new(2, 1));
+        assert!(Location::new(2, 1) < Location::new(2, 3));
+        assert_eq!(
+            Location::new(3, 4).cmp(&Location::new(3, 4)),
+            std::cmp::Ordering::Equal
+        );
+
+        let mut locations = vec![
+            Location::new(2, 1),
+            Location::new(1, 9),
+            Location::new(1, 2),
+        ];
+        locations.sort();
+        assert_eq!(
+            locations,
+            [
+                Location::new(1, 2),
+                Location::new(1, 9),
+                Location::new(2, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_eval_error_display() {
+        let err = EvalError::Overflow {
+            op: Some(OpKind::Mul),
+            lhs: f64::MAX,
+            rhs: Some(2.0),
+        };
+        assert_eq!(
+            err.to_string(),
+            "Numeric overflow in multiplication (operands 1.7976931348623157e308 and 2)"
+        );
+
+        let err = EvalError::Overflow {
+            op: None,
+            lhs: f64::INFINITY,
+            rhs: None,
+        };
+        assert_eq!(err.to_string(), "Numeric overflow (operand inf)");
+
+        let err = EvalError::DivisionByZero {
+            kind: DivisorKind::Literal,
+            dividend: 1.5,
+        };
+        assert_eq!(err.to_string(), "Division by zero: 1.5 / 0");
+
+        let err = EvalError::DomainError("0 raised to a negative power".to_string());
+        assert_eq!(
+            err.to_string(),
+            "Domain error: 0 raised to a negative power"
+        );
+
+        let err = EvalError::ArityMismatch {
+            name: "sqrt".to_string(),
+            expected: 1,
+            found: 2,
+        };
+        assert_eq!(err.to_string(), "Function sqrt takes 1 argument, got 2");
+    }
+
+    #[test]
+    fn test_parse_error_source() {
+        use std::error::Error;
+
+        let lexer_error = LexerError::new("Unexpected character: '@'", Location::new(1, 5));
+        let err = ParseError::from(lexer_error);
+        let source = err.source().expect("lexer-originated error has a source");
+        assert_eq!(
+            source.to_string(),
+            "Lexer error at line 1, column 5: Unexpected character: '@'"
+        );
+
+        let err = ParseError::new("Expected expression", Location::new(1, 1));
+        assert!(err.source().is_none());
+    }
+
+    #[test]
+    fn test_io_error_conversion() {
+        let err = ParseError::new("Expected expression, got Eof", Location::new(2, 4));
+        let io_err = std::io::Error::from(err);
+        assert_eq!(io_err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(io_err.to_string().contains("line 2, column 4"));
+
+        let err = LexerError::new("Unexpected character: '@'", Location::new(1, 5));
+        let io_err = std::io::Error::from(err);
+        assert_eq!(io_err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_expr_error() {
+        use std::error::Error;
+
+        let err = ExprError::from(EvalError::NotANumber);
+        assert_eq!(err.to_string(), "Result is not a number");
+        assert_eq!(err.source().unwrap().to_string(), "Result is not a number");
+
+        let err = ExprError::from(ParseError::new("empty expression", Location::new(1, 1)));
+        assert_eq!(
+            err.to_string(),
+            "Parse error at line 1, column 1: empty expression"
+        );
+
+        let lexer_error = LexerError::new("Unexpected character: '@'", Location::new(1, 5));
+        let err = ExprError::from(ParseError::from_lexer_error(lexer_error));
+        assert!(matches!(err, ExprError::Lexer(_)));
+        assert_eq!(
+            err.to_string(),
+            "Lexer error at line 1, column 5: Unexpected character: '@'"
+        );
+    }
+
+    #[test]
+    fn test_location_helpers() {
+        assert_eq!(Location::new(1, 5).advance_column(3), Location::new(1, 8));
+        assert_eq!(Location::new(1, 5).next_line(), Location::new(2, 1));
+    }
+}
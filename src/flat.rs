@@ -0,0 +1,261 @@
+use crate::ast::Expr;
+
+/// An n-ary view of an expression in which chains of the associative
+/// operators `+` and `*` are collapsed into a single node holding all of
+/// their operands.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FlatExpr {
+    Number(f64),
+    Var(String),
+    Call(String, Vec<FlatExpr>),
+    Sum(Vec<FlatExpr>),
+    Product(Vec<FlatExpr>),
+    Sub(Box<FlatExpr>, Box<FlatExpr>),
+    Div(Box<FlatExpr>, Box<FlatExpr>),
+    Pow(Box<FlatExpr>, Box<FlatExpr>),
+    Neg(Box<FlatExpr>),
+    DoubleFactorial(Box<FlatExpr>),
+    Range(Box<FlatExpr>, Box<FlatExpr>),
+    Let(String, Box<FlatExpr>, Box<FlatExpr>),
+}
+
+impl Expr {
+    /// Converts the expression to its n-ary form, so that `1 + 2 + 3 + 4`
+    /// becomes a single `FlatExpr::Sum` of four operands.
+    pub fn flatten(&self) -> FlatExpr {
+        match self {
+            Expr::Number(n) => FlatExpr::Number(*n),
+            Expr::Var(name) => FlatExpr::Var(name.clone()),
+            Expr::Call(name, args) => {
+                FlatExpr::Call(name.clone(), args.iter().map(Expr::flatten).collect())
+            }
+            Expr::Add(..) => {
+                let mut operands = Vec::new();
+                self.flatten_chain(&mut operands);
+                FlatExpr::Sum(operands)
+            }
+            Expr::Mul(..) => {
+                let mut operands = Vec::new();
+                self.flatten_chain(&mut operands);
+                FlatExpr::Product(operands)
+            }
+            Expr::Sub(a, b) => FlatExpr::Sub(Box::new(a.flatten()), Box::new(b.flatten())),
+            Expr::Div(a, b) => FlatExpr::Div(Box::new(a.flatten()), Box::new(b.flatten())),
+            Expr::Pow(a, b) => FlatExpr::Pow(Box::new(a.flatten()), Box::new(b.flatten())),
+            Expr::Neg(a) => FlatExpr::Neg(Box::new(a.flatten())),
+            Expr::DoubleFactorial(a) => FlatExpr::DoubleFactorial(Box::new(a.flatten())),
+            Expr::Range(a, b) => FlatExpr::Range(Box::new(a.flatten()), Box::new(b.flatten())),
+            Expr::Let { name, value, body } => FlatExpr::Let(
+                name.clone(),
+                Box::new(value.flatten()),
+                Box::new(body.flatten()),
+            ),
+        }
+    }
+
+    /// Folds the constant operands of every `+` and `*` chain into one,
+    /// placed after the remaining operands: `1 + x + 2 + y + 3` becomes
+    /// `(x + y) + 6`.
+    ///
+    /// A literal or negated literal counts as a constant. Because the
+    /// constants are combined out of their original order, the result can
+    /// differ from the input's value by rounding.
+    pub fn combine_constants(&self) -> Expr {
+        self.flatten().combine_constants().unflatten()
+    }
+
+    // Appends the flattened operands of the chain of `self`'s operator.
+    fn flatten_chain(&self, operands: &mut Vec<FlatExpr>) {
+        let (a, b) = match self {
+            Expr::Add(a, b) | Expr::Mul(a, b) => (a, b),
+            _ => unreachable!("not an associative operator: {:?}", self),
+        };
+        for operand in [a, b] {
+            if std::mem::discriminant(operand.as_ref()) == std::mem::discriminant(self) {
+                operand.flatten_chain(operands);
+            } else {
+                operands.push(operand.flatten());
+            }
+        }
+    }
+}
+
+impl FlatExpr {
+    /// Rebuilds a binary tree, folding n-ary operands from the left.
+    ///
+    /// An empty sum is `0` and an empty product is `1`.
+    pub fn unflatten(&self) -> Expr {
+        match self {
+            FlatExpr::Number(n) => Expr::Number(*n),
+            FlatExpr::Var(name) => Expr::Var(name.clone()),
+            FlatExpr::Call(name, args) => {
+                Expr::call(name.clone(), args.iter().map(FlatExpr::unflatten).collect())
+            }
+            FlatExpr::Sum(operands) => Self::fold(operands, Expr::add, 0.0),
+            FlatExpr::Product(operands) => Self::fold(operands, Expr::mul, 1.0),
+            FlatExpr::Sub(a, b) => Expr::sub(a.unflatten(), b.unflatten()),
+            FlatExpr::Div(a, b) => Expr::div(a.unflatten(), b.unflatten()),
+            FlatExpr::Pow(a, b) => Expr::pow(a.unflatten(), b.unflatten()),
+            FlatExpr::Neg(a) => Expr::neg(a.unflatten()),
+            FlatExpr::DoubleFactorial(a) => Expr::double_factorial(a.unflatten()),
+            FlatExpr::Range(a, b) => Expr::range(a.unflatten(), b.unflatten()),
+            FlatExpr::Let(name, value, body) => {
+                Expr::let_in(name.clone(), value.unflatten(), body.unflatten())
+            }
+        }
+    }
+
+    fn combine_constants(self) -> FlatExpr {
+        match self {
+            FlatExpr::Number(_) | FlatExpr::Var(_) => self,
+            FlatExpr::Call(name, args) => FlatExpr::Call(
+                name,
+                args.into_iter().map(FlatExpr::combine_constants).collect(),
+            ),
+            FlatExpr::Sum(operands) => {
+                Self::combine_chain(operands, FlatExpr::Sum, 0.0, |a, b| a + b)
+            }
+            FlatExpr::Product(operands) => {
+                Self::combine_chain(operands, FlatExpr::Product, 1.0, |a, b| a * b)
+            }
+            FlatExpr::Sub(a, b) => FlatExpr::Sub(
+                Box::new(a.combine_constants()),
+                Box::new(b.combine_constants()),
+            ),
+            FlatExpr::Div(a, b) => FlatExpr::Div(
+                Box::new(a.combine_constants()),
+                Box::new(b.combine_constants()),
+            ),
+            FlatExpr::Pow(a, b) => FlatExpr::Pow(
+                Box::new(a.combine_constants()),
+                Box::new(b.combine_constants()),
+            ),
+            FlatExpr::Neg(a) => FlatExpr::Neg(Box::new(a.combine_constants())),
+            FlatExpr::DoubleFactorial(a) => {
+                FlatExpr::DoubleFactorial(Box::new(a.combine_constants()))
+            }
+            FlatExpr::Range(a, b) => FlatExpr::Range(
+                Box::new(a.combine_constants()),
+                Box::new(b.combine_constants()),
+            ),
+            FlatExpr::Let(name, value, body) => FlatExpr::Let(
+                name,
+                Box::new(value.combine_constants()),
+                Box::new(body.combine_constants()),
+            ),
+        }
+    }
+
+    // A chain left with a single operand collapses to it, so that a fully
+    // constant subchain such as `(1 + 2)` counts as a constant in its parent.
+    fn combine_chain(
+        operands: Vec<FlatExpr>,
+        chain: fn(Vec<FlatExpr>) -> FlatExpr,
+        identity: f64,
+        combine: fn(f64, f64) -> f64,
+    ) -> FlatExpr {
+        let mut constant = None;
+        let mut rest = Vec::new();
+        for operand in operands.into_iter().map(FlatExpr::combine_constants) {
+            match operand.constant_value() {
+                Some(value) => constant = Some(combine(constant.unwrap_or(identity), value)),
+                None => rest.push(operand),
+            }
+        }
+        rest.extend(constant.map(FlatExpr::Number));
+        if rest.len() == 1 {
+            rest.pop().expect("one operand")
+        } else {
+            chain(rest)
+        }
+    }
+
+    fn constant_value(&self) -> Option<f64> {
+        match self {
+            FlatExpr::Number(n) => Some(*n),
+            FlatExpr::Neg(a) => match a.as_ref() {
+                FlatExpr::Number(n) => Some(-n),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn fold(operands: &[FlatExpr], combine: fn(Expr, Expr) -> Expr, identity: f64) -> Expr {
+        let mut operands = operands.iter().map(FlatExpr::unflatten);
+        match operands.next() {
+            Some(first) => operands.fold(first, combine),
+            None => Expr::Number(identity),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn parse(input: &str) -> Expr {
+        Parser::new(input).unwrap().parse().unwrap()
+    }
+
+    #[test]
+    fn test_flatten_sum() {
+        let flat = parse("1 + 2 + 3 + 4").flatten();
+        assert_eq!(
+            flat,
+            FlatExpr::Sum(vec![
+                FlatExpr::Number(1.0),
+                FlatExpr::Number(2.0),
+                FlatExpr::Number(3.0),
+                FlatExpr::Number(4.0),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_flatten_nested() {
+        // The product chain is flattened, the subtraction is kept binary
+        let flat = parse("2 * (3 * 4) - 1").flatten();
+        assert_eq!(
+            flat,
+            FlatExpr::Sub(
+                Box::new(FlatExpr::Product(vec![
+                    FlatExpr::Number(2.0),
+                    FlatExpr::Number(3.0),
+                    FlatExpr::Number(4.0),
+                ])),
+                Box::new(FlatExpr::Number(1.0)),
+            )
+        );
+    }
+
+    #[test]
+    fn test_unflatten() {
+        let expr = parse("1 + 2 + 3 * 4 * 5");
+        assert_eq!(expr.flatten().unflatten(), expr);
+        assert_eq!(FlatExpr::Sum(Vec::new()).unflatten(), Expr::Number(0.0));
+    }
+
+    #[test]
+    fn test_combine_constants() {
+        let expr = parse("1 + x + 2 + y + 3");
+        assert_eq!(expr.combine_constants(), parse("x + y + 6"));
+
+        // Non-constant subtrees are kept, with their own constants combined
+        let expr = parse("1 + (4 - 3) + 2 + (8 / 2) + 3");
+        let combined = expr.combine_constants();
+        assert_eq!(combined, parse("(4 - 3) + (8 / 2) + 6"));
+        assert_eq!(combined.eval().unwrap(), expr.eval().unwrap());
+
+        // Negated literals and all-constant subchains fold into the parent
+        let expr = parse("2 * (5 - 1) * -3 * (1 + 2 + 3)");
+        let combined = expr.combine_constants();
+        assert_eq!(combined, Expr::mul(parse("5 - 1"), Expr::number(-36.0)));
+        assert_eq!(combined.eval().unwrap(), expr.eval().unwrap());
+
+        // Constants inside non-commutative operators are combined in place
+        let expr = parse("(1 + 2 + 3) - (4 * 5)");
+        assert_eq!(expr.combine_constants(), parse("6 - 20"));
+    }
+}
@@ -0,0 +1,266 @@
+use crate::ast::NanMode;
+use crate::error::EvalError;
+
+/// A built-in function callable as `name(args)`.
+pub struct Builtin {
+    pub name: &'static str,
+    pub arity: usize,
+    apply: fn(&[f64]) -> f64,
+    /// The arguments a one-argument function is defined for, if not all.
+    domain: Option<Domain>,
+}
+
+/// How close to a pole of `tan` an argument may come, measured by the
+/// cosine of the argument, which vanishes at the poles.
+const POLE_TOLERANCE: f64 = 1e-12;
+
+/// The arguments a one-argument built-in is defined for.
+#[derive(Clone, Copy)]
+enum Domain {
+    /// `[-1, 1]`.
+    UnitInterval,
+    NonNegative,
+    Positive,
+    /// Everywhere except within [`POLE_TOLERANCE`] of the odd multiples
+    /// of π/2.
+    AwayFromPoles,
+}
+
+impl Domain {
+    /// Why `arg` is outside the domain, or `None` if it is inside. NaN is
+    /// let through, to be handled like any other NaN.
+    fn violation(self, arg: f64) -> Option<&'static str> {
+        let inside = match self {
+            Domain::UnitInterval => (-1.0..=1.0).contains(&arg),
+            Domain::NonNegative => arg >= 0.0,
+            Domain::Positive => arg > 0.0,
+            Domain::AwayFromPoles => arg.cos().abs() >= POLE_TOLERANCE,
+        };
+        if inside || arg.is_nan() {
+            return None;
+        }
+        Some(match self {
+            Domain::UnitInterval => "is outside [-1, 1]",
+            Domain::NonNegative => "is negative",
+            Domain::Positive => "is not positive",
+            Domain::AwayFromPoles => "is at a pole",
+        })
+    }
+}
+
+const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "abs",
+        arity: 1,
+        apply: |args| args[0].abs(),
+        domain: None,
+    },
+    Builtin {
+        name: "sqrt",
+        arity: 1,
+        apply: |args| args[0].sqrt(),
+        domain: Some(Domain::NonNegative),
+    },
+    Builtin {
+        name: "exp",
+        arity: 1,
+        apply: |args| args[0].exp(),
+        domain: None,
+    },
+    Builtin {
+        name: "ln",
+        arity: 1,
+        apply: |args| args[0].ln(),
+        domain: Some(Domain::Positive),
+    },
+    Builtin {
+        name: "log",
+        arity: 1,
+        apply: |args| args[0].log10(),
+        domain: Some(Domain::Positive),
+    },
+    Builtin {
+        name: "sin",
+        arity: 1,
+        apply: |args| args[0].sin(),
+        domain: None,
+    },
+    Builtin {
+        name: "cos",
+        arity: 1,
+        apply: |args| args[0].cos(),
+        domain: None,
+    },
+    Builtin {
+        name: "tan",
+        arity: 1,
+        apply: |args| args[0].tan(),
+        domain: Some(Domain::AwayFromPoles),
+    },
+    Builtin {
+        name: "asin",
+        arity: 1,
+        apply: |args| args[0].asin(),
+        domain: Some(Domain::UnitInterval),
+    },
+    Builtin {
+        name: "acos",
+        arity: 1,
+        apply: |args| args[0].acos(),
+        domain: Some(Domain::UnitInterval),
+    },
+    Builtin {
+        name: "floor",
+        arity: 1,
+        apply: |args| args[0].floor(),
+        domain: None,
+    },
+    Builtin {
+        name: "ceil",
+        arity: 1,
+        apply: |args| args[0].ceil(),
+        domain: None,
+    },
+    Builtin {
+        name: "round",
+        arity: 1,
+        apply: |args| args[0].round(),
+        domain: None,
+    },
+    Builtin {
+        name: "min",
+        arity: 2,
+        apply: |args| args[0].min(args[1]),
+        domain: None,
+    },
+    Builtin {
+        name: "max",
+        arity: 2,
+        apply: |args| args[0].max(args[1]),
+        domain: None,
+    },
+];
+
+/// Looks up a built-in function by name.
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+/// Applies the built-in `name` to already evaluated arguments. Under
+/// `NanMode::Error`, an argument outside the function's domain fails with
+/// `EvalError::DomainError`; under `NanMode::Propagate` the IEEE 754
+/// result, such as NaN for `sqrt(-1)`, is returned instead. The result is
+/// not checked for NaN or overflow.
+///
+/// With `degrees`, `sin`, `cos` and `tan` take their argument in degrees
+/// and `asin` and `acos` give their result in degrees. A domain error still
+/// reports the argument as given.
+pub(crate) fn call(
+    name: &str,
+    args: &[f64],
+    degrees: bool,
+    nan: NanMode,
+) -> Result<f64, EvalError> {
+    let builtin = lookup(name).ok_or_else(|| EvalError::UnknownFunction(name.to_string()))?;
+    if args.len() != builtin.arity {
+        return Err(EvalError::ArityMismatch {
+            name: name.to_string(),
+            expected: builtin.arity,
+            found: args.len(),
+        });
+    }
+    let inputs: Vec<f64> = if degrees && matches!(name, "sin" | "cos" | "tan") {
+        args.iter().map(|arg| arg.to_radians()).collect()
+    } else {
+        args.to_vec()
+    };
+    if nan == NanMode::Error
+        && let Some(domain) = builtin.domain
+        && let Some(reason) = domain.violation(inputs[0])
+    {
+        return Err(EvalError::DomainError(format!(
+            "{} of {}, which {}",
+            name, args[0], reason
+        )));
+    }
+    let result = (builtin.apply)(&inputs);
+    if degrees && matches!(name, "asin" | "acos") {
+        Ok(result.to_degrees())
+    } else {
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_call() {
+        assert_eq!(call("sqrt", &[16.0], false, NanMode::Error).unwrap(), 4.0);
+        assert_eq!(
+            call("max", &[2.0, 3.0], false, NanMode::Error).unwrap(),
+            3.0
+        );
+        assert!(matches!(
+            call("nope", &[1.0], false, NanMode::Error),
+            Err(EvalError::UnknownFunction(name)) if name == "nope"
+        ));
+        assert!(matches!(
+            call("abs", &[1.0, 2.0], false, NanMode::Error),
+            Err(EvalError::ArityMismatch {
+                expected: 1,
+                found: 2,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_call_domain() {
+        let message = |name, arg| match call(name, &[arg], false, NanMode::Error) {
+            Err(EvalError::DomainError(message)) => message,
+            other => panic!("{}({}) gave {:?}", name, arg, other),
+        };
+        assert_eq!(message("asin", 2.0), "asin of 2, which is outside [-1, 1]");
+        assert_eq!(
+            message("acos", -3.0),
+            "acos of -3, which is outside [-1, 1]"
+        );
+        assert_eq!(message("sqrt", -4.0), "sqrt of -4, which is negative");
+        assert_eq!(message("ln", -1.0), "ln of -1, which is not positive");
+        assert_eq!(message("log", 0.0), "log of 0, which is not positive");
+        assert_eq!(
+            message("tan", std::f64::consts::FRAC_PI_2),
+            "tan of 1.5707963267948966, which is at a pole"
+        );
+        assert!(message("tan", -3.0 * std::f64::consts::FRAC_PI_2).ends_with("at a pole"));
+
+        // The edges of each domain are inside it
+        assert_eq!(
+            call("asin", &[1.0], false, NanMode::Error).unwrap(),
+            std::f64::consts::FRAC_PI_2
+        );
+        assert_eq!(call("acos", &[1.0], false, NanMode::Error).unwrap(), 0.0);
+        assert_eq!(call("sqrt", &[0.0], false, NanMode::Error).unwrap(), 0.0);
+        assert_eq!(call("log", &[1000.0], false, NanMode::Error).unwrap(), 3.0);
+        assert_eq!(call("tan", &[0.0], false, NanMode::Error).unwrap(), 0.0);
+        assert!(call("tan", &[1.57], false, NanMode::Error).is_ok());
+        // NaN is not a domain error
+        assert!(
+            call("ln", &[f64::NAN], false, NanMode::Error)
+                .unwrap()
+                .is_nan()
+        );
+        // Under NanMode::Propagate the IEEE result is returned
+        assert!(
+            call("asin", &[2.0], false, NanMode::Propagate)
+                .unwrap()
+                .is_nan()
+        );
+        assert_eq!(
+            call("ln", &[0.0], false, NanMode::Propagate).unwrap(),
+            f64::NEG_INFINITY
+        );
+    }
+}
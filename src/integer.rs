@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+
+use crate::ast::Expr;
+use crate::error::{DivisorKind, EvalError, OpKind};
+
+/// 2^53, the magnitude from which integer literals may have been rounded.
+const MAX_EXACT: f64 = 9007199254740992.0;
+
+impl Expr {
+    /// Evaluates the expression exactly in `i64` arithmetic.
+    ///
+    /// Every literal must be a whole number and every division exact, or
+    /// evaluation fails with `EvalError::NonIntegerResult`; so `6 / 2` is 3
+    /// but `7 / 2` is an error. Results that do not fit in an `i64` are an
+    /// `EvalError::Overflow`. Unlike `eval`, intermediate results above 2^53
+    /// keep full precision. A literal of 2^53 or more in magnitude fails with
+    /// `EvalError::ImpreciseLiteral`: an `Expr::Number` holds an `f64`, so
+    /// a literal such as `9007199254740993` may already have been rounded.
+    /// No functions are defined, and the only variables are those bound by
+    /// `let`.
+    pub fn eval_integer(&self) -> Result<i64, EvalError> {
+        self.eval_integer_in(&HashMap::new())
+    }
+
+    fn eval_integer_in(&self, scope: &HashMap<String, i64>) -> Result<i64, EvalError> {
+        match self {
+            Expr::Number(n) => {
+                if !n.is_finite() || n.fract() != 0.0 {
+                    return Err(EvalError::NonIntegerResult);
+                }
+                // 2^53 + 1 rounds to 2^53, so 2^53 itself is ambiguous
+                if n.abs() >= MAX_EXACT {
+                    return Err(EvalError::ImpreciseLiteral(*n));
+                }
+                Ok(*n as i64)
+            }
+            Expr::Neg(a) => {
+                let value = a.eval_integer_in(scope)?;
+                value.checked_neg().ok_or(EvalError::Overflow {
+                    op: Some(OpKind::Neg),
+                    lhs: value as f64,
+                    rhs: None,
+                })
+            }
+            Expr::DoubleFactorial(a) => {
+                let value = a.eval_integer_in(scope)?;
+                if value < 0 {
+                    return Err(EvalError::DomainError(format!(
+                        "double factorial of {}, which is not a non-negative integer",
+                        value
+                    )));
+                }
+                (1..=value)
+                    .rev()
+                    .step_by(2)
+                    .try_fold(1i64, i64::checked_mul)
+                    .ok_or(EvalError::Overflow {
+                        op: Some(OpKind::DoubleFactorial),
+                        lhs: value as f64,
+                        rhs: None,
+                    })
+            }
+            Expr::Add(a, b) => Self::checked(a, b, scope, OpKind::Add, i64::checked_add),
+            Expr::Sub(a, b) => Self::checked(a, b, scope, OpKind::Sub, i64::checked_sub),
+            Expr::Mul(a, b) => Self::checked(a, b, scope, OpKind::Mul, i64::checked_mul),
+            Expr::Div(a, b) => {
+                let dividend = a.eval_integer_in(scope)?;
+                let divisor = b.eval_integer_in(scope)?;
+                if divisor == 0 {
+                    let kind = if b.is_literal() {
+                        DivisorKind::Literal
+                    } else {
+                        DivisorKind::Computed
+                    };
+                    return Err(EvalError::DivisionByZero {
+                        kind,
+                        dividend: dividend as f64,
+                    });
+                }
+                if dividend.checked_rem(divisor) != Some(0) {
+                    return Err(if dividend == i64::MIN && divisor == -1 {
+                        EvalError::Overflow {
+                            op: Some(OpKind::Div),
+                            lhs: dividend as f64,
+                            rhs: Some(divisor as f64),
+                        }
+                    } else {
+                        EvalError::NonIntegerResult
+                    });
+                }
+                Ok(dividend / divisor)
+            }
+            Expr::Pow(a, b) => {
+                let base = a.eval_integer_in(scope)?;
+                let exponent = b.eval_integer_in(scope)?;
+                let overflow = EvalError::Overflow {
+                    op: Some(OpKind::Pow),
+                    lhs: base as f64,
+                    rhs: Some(exponent as f64),
+                };
+                match (base, exponent) {
+                    (1, _) => Ok(1),
+                    (-1, _) => Ok(if exponent % 2 == 0 { 1 } else { -1 }),
+                    (_, e) if e < 0 => Err(EvalError::NonIntegerResult),
+                    (0, _) => Ok(if exponent == 0 { 1 } else { 0 }),
+                    _ => u32::try_from(exponent)
+                        .ok()
+                        .and_then(|e| base.checked_pow(e))
+                        .ok_or(overflow),
+                }
+            }
+            Expr::Range(..) => Err(EvalError::NotAScalar),
+            Expr::Var(name) => scope
+                .get(name)
+                .copied()
+                .ok_or_else(|| EvalError::UndefinedVariable(name.clone())),
+            Expr::Call(name, _) => Err(EvalError::UnknownFunction(name.clone())),
+            Expr::Let { name, value, body } => {
+                let value = value.eval_integer_in(scope)?;
+                let mut scope = scope.clone();
+                scope.insert(name.clone(), value);
+                body.eval_integer_in(&scope)
+            }
+        }
+    }
+
+    fn checked(
+        a: &Expr,
+        b: &Expr,
+        scope: &HashMap<String, i64>,
+        kind: OpKind,
+        op: fn(i64, i64) -> Option<i64>,
+    ) -> Result<i64, EvalError> {
+        let left = a.eval_integer_in(scope)?;
+        let right = b.eval_integer_in(scope)?;
+        op(left, right).ok_or(EvalError::Overflow {
+            op: Some(kind),
+            lhs: left as f64,
+            rhs: Some(right as f64),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn n(value: f64) -> Expr {
+        Expr::number(value)
+    }
+
+    #[test]
+    fn test_eval_integer() {
+        assert_eq!(Expr::div(n(6.0), n(2.0)).eval_integer().unwrap(), 3);
+        assert_eq!(Expr::pow(n(-2.0), n(3.0)).eval_integer().unwrap(), -8);
+        assert_eq!(
+            Expr::sub(n(2.0), Expr::mul(n(3.0), n(4.0)))
+                .eval_integer()
+                .unwrap(),
+            -10
+        );
+        assert_eq!(Expr::double_factorial(n(9.0)).eval_integer().unwrap(), 945);
+        assert!(matches!(
+            Expr::double_factorial(n(-1.0)).eval_integer(),
+            Err(EvalError::DomainError(_))
+        ));
+    }
+
+    #[test]
+    fn test_eval_integer_non_integer() {
+        assert!(matches!(
+            Expr::div(n(7.0), n(2.0)).eval_integer(),
+            Err(EvalError::NonIntegerResult)
+        ));
+        assert!(matches!(
+            Expr::add(n(1.5), n(1.0)).eval_integer(),
+            Err(EvalError::NonIntegerResult)
+        ));
+        assert!(matches!(
+            Expr::pow(n(2.0), n(-1.0)).eval_integer(),
+            Err(EvalError::NonIntegerResult)
+        ));
+    }
+
+    #[test]
+    fn test_eval_integer_precision() {
+        // 2^53 + 1 is not representable as an f64
+        let expr = Expr::add(Expr::pow(n(2.0), n(53.0)), n(1.0));
+        assert_eq!(expr.eval_integer().unwrap(), 9007199254740993);
+        assert_eq!(expr.eval().unwrap(), 9007199254740992.0);
+
+        // Bound values keep full precision too
+        let expr = Expr::let_in("n", expr, Expr::sub(Expr::var("n"), n(1.0)));
+        assert_eq!(expr.eval_integer().unwrap(), 9007199254740992);
+
+        // A literal from 2^53 up may have been rounded when it was parsed,
+        // so it is rejected rather than silently evaluated
+        let parse = |input| crate::parser::Parser::new(input).unwrap().parse().unwrap();
+        assert!(matches!(
+            parse("9007199254740993").eval_integer(),
+            Err(EvalError::ImpreciseLiteral(_))
+        ));
+        assert!(matches!(
+            parse("9007199254740993 - 9007199254740992").eval_integer(),
+            Err(EvalError::ImpreciseLiteral(_))
+        ));
+        assert!(matches!(
+            parse("-9007199254740994").eval_integer(),
+            Err(EvalError::ImpreciseLiteral(_))
+        ));
+        assert!(matches!(
+            parse("9007199254740992").eval_integer(),
+            Err(EvalError::ImpreciseLiteral(_))
+        ));
+        assert_eq!(
+            parse("9007199254740991 + 2").eval_integer().unwrap(),
+            9007199254740993
+        );
+    }
+
+    #[test]
+    fn test_eval_integer_overflow() {
+        // 2^62 * 2
+        let expr = Expr::mul(Expr::pow(n(2.0), n(62.0)), n(2.0));
+        assert!(matches!(
+            expr.eval_integer(),
+            Err(EvalError::Overflow { .. })
+        ));
+        assert!(matches!(
+            Expr::pow(n(10.0), n(19.0)).eval_integer(),
+            Err(EvalError::Overflow { .. })
+        ));
+    }
+}
@@ -1,18 +1,95 @@
-use crate::error::{LexerError, Location};
+use crate::error::{LexerError, Location, Span};
+use crate::normalize::normalize_char;
 use crate::token::Token;
 
+/// How integer literals with leading zeros, like `007`, are treated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LeadingZeros {
+    /// `007` is the decimal number 7.
+    #[default]
+    Allow,
+    /// A multi-digit integer part starting with `0` is an error. A lone `0`
+    /// (including in `0.5`) is still accepted.
+    Reject,
+}
+
+/// How a number with a decimal point but no following digits, like `1.`,
+/// is treated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrailingDot {
+    /// `1.` is an error.
+    #[default]
+    Error,
+    /// `1.` is read as `1.0`, which is forgiving of half-typed input.
+    AsZero,
+}
+
+/// Options controlling how the lexer reads its input.
+#[derive(Debug, Clone, Copy)]
+pub struct LexerConfig {
+    /// Map full-width digits/operators and common lookalikes to ASCII
+    /// before tokenizing (see [`normalize_char`] for the table).
+    pub normalize_input: bool,
+    pub leading_zeros: LeadingZeros,
+    pub trailing_dot: TrailingDot,
+    /// Skip a UTF-8 byte order mark at the very start of the input, as
+    /// written by many Windows editors. The BOM takes up no column.
+    pub strip_bom: bool,
+    /// Treat `#` or `//` up to the end of the line as a comment. This also
+    /// skips a leading `#!` shebang line in script files.
+    pub comments: bool,
+}
+
+impl Default for LexerConfig {
+    fn default() -> Self {
+        LexerConfig {
+            normalize_input: false,
+            leading_zeros: LeadingZeros::default(),
+            trailing_dot: TrailingDot::default(),
+            strip_bom: true,
+            comments: false,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Lexer<'a> {
-    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    input: &'a str,
+    pos: usize,
     line: usize,
     column: usize,
+    config: LexerConfig,
 }
 
 impl<'a> Lexer<'a> {
     pub fn new(input: &'a str) -> Self {
-        Lexer {
-            chars: input.chars().peekable(),
+        Self::with_config(input, LexerConfig::default())
+    }
+
+    pub fn with_config(input: &'a str, config: LexerConfig) -> Self {
+        let mut lexer = Lexer {
+            input,
+            pos: 0,
             line: 1,
             column: 1,
+            config,
+        };
+        lexer.skip_bom();
+        lexer
+    }
+
+    /// Points the lexer at new input, keeping its configuration.
+    pub fn reset(&mut self, input: &'a str) {
+        self.input = input;
+        self.pos = 0;
+        self.line = 1;
+        self.column = 1;
+        self.skip_bom();
+    }
+
+    fn skip_bom(&mut self) {
+        if self.config.strip_bom && self.input.starts_with('\u{FEFF}') {
+            self.pos = '\u{FEFF}'.len_utf8();
         }
     }
 
@@ -21,35 +98,75 @@ impl<'a> Lexer<'a> {
         Location::new(self.line, self.column)
     }
 
+    /// The span from `start` through the last character read. Only used
+    /// for tokens that cannot cross a line break.
+    fn span_from(&self, start: Location) -> Span {
+        Span::new(start, Location::new(self.line, self.column - 1))
+    }
+
+    fn map_char(&self, c: char) -> char {
+        if self.config.normalize_input {
+            normalize_char(c)
+        } else {
+            c
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..]
+            .chars()
+            .next()
+            .map(|c| self.map_char(c))
+    }
+
+    /// Returns the character after the one `peek` would return.
+    fn peek_second(&self) -> Option<char> {
+        self.input[self.pos..]
+            .chars()
+            .nth(1)
+            .map(|c| self.map_char(c))
+    }
+
     fn advance_char(&mut self) -> Option<char> {
-        let c = self.chars.next();
-        if let Some(ch) = c {
-            if ch == '\n' {
-                self.line += 1;
-                self.column = 1;
-            } else {
-                self.column += 1;
-            }
+        let raw = self.input[self.pos..].chars().next()?;
+        self.pos += raw.len_utf8();
+        let ch = self.map_char(raw);
+        if ch == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
         }
-        c
+        Some(ch)
     }
 
     fn skip_whitespace(&mut self) {
-        while let Some(&c) = self.chars.peek() {
+        while let Some(c) = self.peek() {
             if c.is_whitespace() {
                 self.advance_char();
+            } else if self.config.comments
+                && (c == '#' || (c == '/' && self.peek_second() == Some('/')))
+            {
+                self.skip_line();
             } else {
                 break;
             }
         }
     }
 
-    fn read_number(&mut self) -> Result<f64, LexerError> {
+    /// Skips up to, but not including, the next newline.
+    fn skip_line(&mut self) {
+        while self.peek().is_some_and(|c| c != '\n') {
+            self.advance_char();
+        }
+    }
+
+    fn read_number(&mut self) -> Result<Token, LexerError> {
         let start_location = self.location();
         let mut num_str = String::new();
 
         // Integer part
-        while let Some(&c) = self.chars.peek() {
+        while let Some(c) = self.peek() {
             if c.is_ascii_digit() {
                 num_str.push(c);
                 self.advance_char();
@@ -58,13 +175,30 @@ impl<'a> Lexer<'a> {
             }
         }
 
-        // Decimal part
-        if self.chars.peek() == Some(&'.') {
+        if self.config.leading_zeros == LeadingZeros::Reject
+            && num_str.len() > 1
+            && num_str.starts_with('0')
+        {
+            return Err(LexerError::new(
+                format!("Leading zeros are not allowed: {}", num_str),
+                start_location,
+            )
+            .with_span(self.span_from(start_location)));
+        }
+
+        // Leading-decimal literals like `.5` have an empty integer part
+        if num_str.is_empty() {
+            num_str.push('0');
+        }
+
+        // Decimal part. A `.` followed by another `.` is the range operator,
+        // so `1..2` is `1`, `..`, `2` rather than a malformed `1.`.
+        if self.peek() == Some('.') && self.peek_second() != Some('.') {
             num_str.push('.');
             self.advance_char();
 
             let mut has_decimal_digits = false;
-            while let Some(&c) = self.chars.peek() {
+            while let Some(c) = self.peek() {
                 if c.is_ascii_digit() {
                     num_str.push(c);
                     self.advance_char();
@@ -75,16 +209,86 @@ impl<'a> Lexer<'a> {
             }
 
             if !has_decimal_digits {
-                return Err(LexerError::new(
-                    "Expected digits after decimal point",
-                    start_location,
-                ));
+                if self.config.trailing_dot == TrailingDot::AsZero {
+                    num_str.push('0');
+                } else {
+                    return Err(LexerError::new(
+                        "Expected digits after decimal point",
+                        start_location,
+                    )
+                    .with_span(self.span_from(start_location)));
+                }
+            }
+        }
+
+        // Exponent part, like the `e-3` in `1.5e-3`
+        let mantissa_len = num_str.len();
+        if let Some(e @ ('e' | 'E')) = self.peek() {
+            num_str.push(e);
+            self.advance_char();
+            if let Some(sign @ ('+' | '-')) = self.peek() {
+                num_str.push(sign);
+                self.advance_char();
+            }
+
+            let mut has_exponent_digits = false;
+            while let Some(c) = self.peek() {
+                if c.is_ascii_digit() {
+                    num_str.push(c);
+                    self.advance_char();
+                    has_exponent_digits = true;
+                } else {
+                    break;
+                }
+            }
+
+            if !has_exponent_digits {
+                return Err(
+                    LexerError::new("Expected digits in exponent", start_location)
+                        .with_span(self.span_from(start_location)),
+                );
             }
         }
 
-        num_str
-            .parse()
-            .map_err(|_| LexerError::new(format!("Invalid number: {}", num_str), start_location))
+        let span = self.span_from(start_location);
+        let value: f64 = num_str.parse().map_err(|_| {
+            LexerError::new(format!("Invalid number: {}", num_str), start_location).with_span(span)
+        })?;
+
+        // A nonzero literal too small for f64 silently parses as 0.0, which
+        // would turn e.g. a tiny divisor into a division by zero.
+        if value == 0.0
+            && num_str[..mantissa_len]
+                .chars()
+                .any(|c| ('1'..='9').contains(&c))
+        {
+            return Err(LexerError::new(
+                format!("Number literal underflows to zero: {}", num_str),
+                start_location,
+            )
+            .with_span(span));
+        }
+        Ok(Token::Number {
+            value,
+            exact: is_exact(&num_str, value),
+        })
+    }
+
+    fn read_identifier(&mut self) -> Token {
+        let mut name = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                name.push(c);
+                self.advance_char();
+            } else {
+                break;
+            }
+        }
+        match name.as_str() {
+            "let" => Token::Let,
+            "in" => Token::In,
+            _ => Token::Identifier(name),
+        }
     }
 
     pub fn next_token(&mut self) -> Result<(Token, Location), LexerError> {
@@ -92,10 +296,23 @@ impl<'a> Lexer<'a> {
 
         let location = self.location();
 
-        match self.chars.peek() {
+        match self.peek() {
             None => Ok((Token::Eof, location)),
-            Some(&c) => match c {
-                '0'..='9' => Ok((Token::Number(self.read_number()?), location)),
+            Some(c) => match c {
+                '0'..='9' => Ok((self.read_number()?, location)),
+                'a'..='z' | 'A'..='Z' | '_' => Ok((self.read_identifier(), location)),
+                '.' if self.peek_second() == Some('.') => {
+                    self.advance_char();
+                    self.advance_char();
+                    Ok((Token::DotDot, location))
+                }
+                '.' if self.peek_second().is_some_and(|c| c.is_ascii_digit()) => {
+                    Ok((self.read_number()?, location))
+                }
+                '.' => Err(LexerError::new(
+                    "unexpected '.'; expected digits to form a number",
+                    location,
+                )),
                 '+' => {
                     self.advance_char();
                     Ok((Token::Plus, location))
@@ -112,6 +329,22 @@ impl<'a> Lexer<'a> {
                     self.advance_char();
                     Ok((Token::Slash, location))
                 }
+                '^' => {
+                    self.advance_char();
+                    Ok((Token::Caret, location))
+                }
+                // `!!` is one token, read greedily, so `5!!!!` is two double
+                // factorials. A `!` not part of a pair is an error rather
+                // than a factorial.
+                '!' if self.peek_second() == Some('!') => {
+                    self.advance_char();
+                    self.advance_char();
+                    Ok((Token::DoubleBang, location))
+                }
+                '!' => Err(LexerError::new(
+                    "Unexpected character: '!'; only the double factorial '!!' is supported",
+                    location,
+                )),
                 '(' => {
                     self.advance_char();
                     Ok((Token::LeftParen, location))
@@ -120,6 +353,14 @@ impl<'a> Lexer<'a> {
                     self.advance_char();
                     Ok((Token::RightParen, location))
                 }
+                ',' => {
+                    self.advance_char();
+                    Ok((Token::Comma, location))
+                }
+                '=' => {
+                    self.advance_char();
+                    Ok((Token::Equals, location))
+                }
                 _ => Err(LexerError::new(
                     format!("Unexpected character: '{}'", c),
                     location,
@@ -127,12 +368,117 @@ impl<'a> Lexer<'a> {
             },
         }
     }
+
+    /// Like `next_token`, but also returns the exact source text the token
+    /// was read from and its span, e.g. for a syntax highlighter. The text
+    /// of `Eof` is empty and its span is the single location past the end.
+    pub fn next_token_with_text(&mut self) -> Result<(Token, &'a str, Span), LexerError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let (token, location) = self.next_token()?;
+        let text = &self.input[start..self.pos];
+        let span = if text.is_empty() {
+            Span::new(location, location)
+        } else {
+            self.span_from(location)
+        };
+        Ok((token, text, span))
+    }
+}
+
+/// Whether `value` is exactly the decimal number written as `literal`.
+///
+/// A finite `f64` has a finite decimal expansion; this renders it in full
+/// and compares it with the literal, ignoring insignificant zeros.
+fn is_exact(literal: &str, value: f64) -> bool {
+    if value == 0.0 {
+        // The lexer has already rejected nonzero literals that read as zero
+        return true;
+    }
+    if value.is_infinite() {
+        return false;
+    }
+    let exact_digits = format!("{:.*}", fraction_digits(value), value);
+    trim_decimal(&exact_digits) == trim_decimal(&expand_exponent(literal))
+}
+
+/// Rewrites a literal with an exponent, like `1.5e-3`, as the plain decimal
+/// `0.0015`. For a literal with a finite nonzero value the exponent is at
+/// most a few hundred plus the literal's length, so the result stays small.
+fn expand_exponent(literal: &str) -> String {
+    let Some((mantissa, exponent)) = literal.split_once(['e', 'E']) else {
+        return literal.to_string();
+    };
+    let exponent: i64 = exponent
+        .parse()
+        .expect("the lexer only reads digit exponents");
+    let (int_part, frac_part) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{}{}", int_part, frac_part);
+    let point = int_part.len() as i64 + exponent;
+    if point <= 0 {
+        format!("0.{}{}", "0".repeat(point.unsigned_abs() as usize), digits)
+    } else if point as usize >= digits.len() {
+        format!("{}{}", digits, "0".repeat(point as usize - digits.len()))
+    } else {
+        let (whole, fraction) = digits.split_at(point as usize);
+        format!("{}.{}", whole, fraction)
+    }
+}
+
+/// Returns how many decimal digits the exact expansion of `value` has after
+/// the decimal point: one per binary digit below the ones place.
+fn fraction_digits(value: f64) -> usize {
+    let bits = value.to_bits();
+    let mantissa = bits & ((1 << 52) - 1);
+    let biased_exponent = ((bits >> 52) & 0x7ff) as i32;
+    let (mantissa, exponent) = if biased_exponent == 0 {
+        (mantissa, -1074)
+    } else {
+        (mantissa | (1 << 52), biased_exponent - 1075)
+    };
+    if mantissa == 0 {
+        return 0;
+    }
+    (-(exponent + mantissa.trailing_zeros() as i32)).max(0) as usize
+}
+
+/// Strips leading zeros from the integer part and trailing zeros from the
+/// fractional part of a plain decimal number.
+fn trim_decimal(number: &str) -> String {
+    let (int_part, frac_part) = number.split_once('.').unwrap_or((number, ""));
+    let int_part = int_part.trim_start_matches('0');
+    let frac_part = frac_part.trim_end_matches('0');
+    let int_part = if int_part.is_empty() { "0" } else { int_part };
+    if frac_part.is_empty() {
+        int_part.to_string()
+    } else {
+        format!("{}.{}", int_part, frac_part)
+    }
+}
+
+/// Runs the lexer over `input` to the end and returns every token,
+/// including the final `Token::Eof`. Intended for diagnosing parse bugs.
+pub fn debug_tokens(input: &str) -> Result<Vec<Token>, LexerError> {
+    let mut lexer = Lexer::new(input);
+    let mut tokens = Vec::new();
+    loop {
+        let (token, _) = lexer.next_token()?;
+        let done = token == Token::Eof;
+        tokens.push(token);
+        if done {
+            return Ok(tokens);
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn number(value: f64) -> Token {
+        Token::Number { value, exact: true }
+    }
+
     #[test]
     fn test_lexer() {
         let mut lexer = Lexer::new("2 + 2 * 2.5");
@@ -157,13 +503,284 @@ mod tests {
         assert_eq!(err.location.column, 5);
     }
 
+    #[test]
+    fn test_lexer_underflow_literal() {
+        let mut lexer = Lexer::new("5e-400");
+        let err = lexer.next_token().unwrap_err();
+        assert!(err.message.contains("underflows to zero"));
+        assert_eq!(err.location.column, 1);
+
+        let input = format!("0.{}5", "0".repeat(399));
+        let mut lexer = Lexer::new(&input);
+        assert!(lexer.next_token().is_err());
+
+        let mut lexer = Lexer::new("0.000");
+        assert_eq!(lexer.next_token().unwrap().0, number(0.0));
+        let mut lexer = Lexer::new("0e-400");
+        assert_eq!(lexer.next_token().unwrap().0, number(0.0));
+    }
+
+    #[test]
+    fn test_lexer_leading_decimal() {
+        let mut lexer = Lexer::new(".5");
+        assert_eq!(lexer.next_token().unwrap().0, number(0.5));
+    }
+
+    #[test]
+    fn test_lexer_standalone_dot() {
+        let mut lexer = Lexer::new(".");
+        let err = lexer.next_token().unwrap_err();
+        assert_eq!(
+            err.message,
+            "unexpected '.'; expected digits to form a number"
+        );
+        assert_eq!(err.location.column, 1);
+
+        let mut lexer = Lexer::new("1 + .");
+        assert!(lexer.next_token().is_ok()); // 1
+        assert!(lexer.next_token().is_ok()); // +
+        let err = lexer.next_token().unwrap_err();
+        assert_eq!(
+            err.message,
+            "unexpected '.'; expected digits to form a number"
+        );
+        assert_eq!(err.location.column, 5);
+    }
+
+    #[test]
+    fn test_lexer_leading_zeros() {
+        let reject = LexerConfig {
+            leading_zeros: LeadingZeros::Reject,
+            ..LexerConfig::default()
+        };
+
+        let mut lexer = Lexer::new("007");
+        assert_eq!(lexer.next_token().unwrap().0, number(7.0));
+        let mut lexer = Lexer::with_config("007", reject);
+        let err = lexer.next_token().unwrap_err();
+        assert!(err.message.contains("Leading zeros"));
+
+        for config in [LexerConfig::default(), reject] {
+            let mut lexer = Lexer::with_config("0 0.5", config);
+            assert_eq!(lexer.next_token().unwrap().0, number(0.0));
+            assert_eq!(lexer.next_token().unwrap().0, number(0.5));
+        }
+    }
+
+    #[test]
+    fn test_next_token_with_text() {
+        let mut lexer = Lexer::new("12.5 + x\n  !!3e2");
+        let mut tokens = Vec::new();
+        loop {
+            let (token, text, span) = lexer.next_token_with_text().unwrap();
+            let done = token == Token::Eof;
+            tokens.push((token, text, span));
+            if done {
+                break;
+            }
+        }
+        let span =
+            |line, start, end| Span::new(Location::new(line, start), Location::new(line, end));
+        assert_eq!(
+            tokens,
+            vec![
+                (number(12.5), "12.5", span(1, 1, 4)),
+                (Token::Plus, "+", span(1, 6, 6)),
+                (Token::Identifier("x".to_string()), "x", span(1, 8, 8)),
+                (Token::DoubleBang, "!!", span(2, 3, 4)),
+                (number(300.0), "3e2", span(2, 5, 7)),
+                (Token::Eof, "", span(2, 8, 8)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_debug_tokens() {
+        assert_eq!(
+            debug_tokens("2 + 3").unwrap(),
+            [number(2.0), Token::Plus, number(3.0), Token::Eof]
+        );
+        assert_eq!(
+            debug_tokens("2^3").unwrap(),
+            [number(2.0), Token::Caret, number(3.0), Token::Eof]
+        );
+        assert!(debug_tokens("2 + @").is_err());
+    }
+
+    #[test]
+    fn test_lexer_double_bang() {
+        assert_eq!(
+            debug_tokens("5!!!!").unwrap(),
+            [
+                number(5.0),
+                Token::DoubleBang,
+                Token::DoubleBang,
+                Token::Eof
+            ]
+        );
+        assert_eq!(
+            debug_tokens("5 !! + 1").unwrap(),
+            [
+                number(5.0),
+                Token::DoubleBang,
+                Token::Plus,
+                number(1.0),
+                Token::Eof
+            ]
+        );
+
+        let err = debug_tokens("5!!!").unwrap_err();
+        assert_eq!(err.location, Location::new(1, 4));
+        assert!(debug_tokens("5! !").is_err());
+    }
+
+    #[test]
+    fn test_lexer_trailing_dot() {
+        let as_zero = LexerConfig {
+            trailing_dot: TrailingDot::AsZero,
+            ..LexerConfig::default()
+        };
+
+        let mut lexer = Lexer::new("1.");
+        assert!(lexer.next_token().is_err());
+        let mut lexer = Lexer::with_config("1.", as_zero);
+        assert_eq!(lexer.next_token().unwrap().0, number(1.0));
+
+        for config in [LexerConfig::default(), as_zero] {
+            let mut lexer = Lexer::with_config("1.5", config);
+            assert_eq!(lexer.next_token().unwrap().0, number(1.5));
+        }
+    }
+
+    #[test]
+    fn test_lexer_trailing_dot_span() {
+        let mut lexer = Lexer::new("12.");
+        let err = lexer.next_token().unwrap_err();
+        assert_eq!(
+            err.span,
+            Some(Span::new(Location::new(1, 1), Location::new(1, 3)))
+        );
+    }
+
+    #[test]
+    fn test_lexer_strip_bom() {
+        let mut lexer = Lexer::new("\u{FEFF}2 + 3");
+        let (token, location) = lexer.next_token().unwrap();
+        assert_eq!(token, number(2.0));
+        assert_eq!(location.column, 1);
+
+        let strict = LexerConfig {
+            strip_bom: false,
+            ..LexerConfig::default()
+        };
+        let mut lexer = Lexer::with_config("\u{FEFF}2 + 3", strict);
+        assert!(lexer.next_token().is_err());
+    }
+
+    #[test]
+    fn test_lexer_number_exactness() {
+        let exact = |input| match Lexer::new(input).next_token().unwrap().0 {
+            Token::Number { exact, .. } => exact,
+            token => panic!("expected a number, got {:?}", token),
+        };
+        assert!(exact("0.25"));
+        assert!(exact("3"));
+        assert!(exact("007.500"));
+        assert!(exact("9007199254740992"));
+        assert!(!exact("0.1"));
+        assert!(!exact("9007199254740993"));
+        assert!(exact("2.5e-1"));
+        assert!(exact("9007199254740992e0"));
+        assert!(exact("1e22"));
+        assert!(!exact("1e23"));
+        assert!(!exact("1e-1"));
+        assert!(!exact("1e400"));
+    }
+
     #[test]
     fn test_lexer_multiline() {
         let mut lexer = Lexer::new("1 +\n2");
         assert!(lexer.next_token().is_ok()); // 1
         assert!(lexer.next_token().is_ok()); // +
         let (token, location) = lexer.next_token().unwrap();
-        assert_eq!(token, Token::Number(2.0));
+        assert_eq!(token, number(2.0));
         assert_eq!(location.line, 2);
     }
+
+    #[test]
+    fn test_lexer_range() {
+        assert_eq!(
+            debug_tokens("1..2").unwrap(),
+            [number(1.0), Token::DotDot, number(2.0), Token::Eof]
+        );
+        assert_eq!(
+            debug_tokens("0.5..1.5").unwrap(),
+            [number(0.5), Token::DotDot, number(1.5), Token::Eof]
+        );
+        assert_eq!(debug_tokens("1.5").unwrap(), [number(1.5), Token::Eof]);
+
+        // `..` wins over a trailing dot even when that is accepted
+        let as_zero = LexerConfig {
+            trailing_dot: TrailingDot::AsZero,
+            ..LexerConfig::default()
+        };
+        let mut lexer = Lexer::with_config("1..2", as_zero);
+        assert_eq!(lexer.next_token().unwrap().0, number(1.0));
+        assert_eq!(lexer.next_token().unwrap().0, Token::DotDot);
+    }
+
+    #[test]
+    fn test_lexer_identifiers() {
+        assert_eq!(
+            debug_tokens("x_1 * 2y").unwrap(),
+            [
+                Token::Identifier("x_1".to_string()),
+                Token::Star,
+                number(2.0),
+                Token::Identifier("y".to_string()),
+                Token::Eof
+            ]
+        );
+        assert!(debug_tokens("1 + é").is_err());
+    }
+
+    #[test]
+    fn test_lexer_keywords() {
+        assert_eq!(
+            debug_tokens("let x = 1 in letter").unwrap(),
+            [
+                Token::Let,
+                Token::Identifier("x".to_string()),
+                Token::Equals,
+                number(1.0),
+                Token::In,
+                Token::Identifier("letter".to_string()),
+                Token::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lexer_exponent() {
+        let value = |input| match Lexer::new(input).next_token().unwrap().0 {
+            Token::Number { value, .. } => value,
+            token => panic!("expected a number, got {:?}", token),
+        };
+        assert_eq!(value("1e10"), 1e10);
+        assert_eq!(value("1.5e-3"), 1.5e-3);
+        assert_eq!(value("2E+4"), 2e4);
+        assert_eq!(value("6.022e23"), 6.022e23);
+        assert_eq!(value(".5e1"), 5.0);
+
+        for input in ["1e", "1e+", "2.5E-"] {
+            let err = Lexer::new(input).next_token().unwrap_err();
+            assert_eq!(err.message, "Expected digits in exponent");
+            assert_eq!(err.location, Location::new(1, 1));
+        }
+        let err = Lexer::new("1e+ 2").next_token().unwrap_err();
+        assert_eq!(
+            err.span,
+            Some(Span::new(Location::new(1, 1), Location::new(1, 3)))
+        );
+    }
 }
@@ -0,0 +1,44 @@
+//! Parsing and evaluation of arithmetic expressions.
+//!
+//! [`eval_str`] evaluates a string in one call. To inspect or transform
+//! the expression first, parse it with [`Parser`] and work with the
+//! resulting [`Expr`].
+
+pub mod ast;
+pub mod batch;
+mod canonical;
+mod derivative;
+pub mod diagnostic;
+pub mod error;
+pub mod flat;
+pub mod function;
+mod integer;
+pub mod lexer;
+pub mod machine;
+pub mod normalize;
+pub mod parser;
+pub mod policy;
+pub mod printer;
+pub mod residual;
+mod rewrite;
+mod rpn;
+mod sexpr;
+pub mod shunting_yard;
+pub mod simplify;
+pub mod source_map;
+pub mod token;
+
+pub use ast::Expr;
+pub use error::{EvalError, ExprError, LexerError, ParseError};
+pub use parser::Parser;
+pub use printer::format_result;
+
+/// Lexes, parses and evaluates `input`, with no variables defined.
+///
+/// ```
+/// assert_eq!(rust_expr_evaluator::eval_str("2 + 3 * 4").unwrap(), 14.0);
+/// ```
+pub fn eval_str(input: &str) -> Result<f64, ExprError> {
+    let expr = Parser::new(input)?.parse()?;
+    Ok(expr.eval()?)
+}
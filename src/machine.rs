@@ -0,0 +1,260 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::ast::{EvalConfig, EvalObserver, Expr, NoopObserver};
+use crate::error::EvalError;
+
+/// The outcome of one [`EvalMachine::step`] call.
+#[derive(Debug, Clone)]
+pub enum StepResult {
+    /// The fuel ran out before evaluation finished.
+    Pending,
+    Done(Result<f64, EvalError>),
+}
+
+/// A unit of pending work on the walk's stack.
+enum Frame<'e> {
+    /// Schedule the node's children, then the node itself.
+    Visit(&'e Expr),
+    /// Combine the node's already evaluated children.
+    Apply(&'e Expr),
+    /// Bind a `let`'s evaluated value, then evaluate its body.
+    Bind(&'e Expr),
+    /// Drop a `let`'s binding once its body has been evaluated.
+    Unbind(&'e Expr),
+}
+
+/// An evaluation in progress, walking the tree with an explicit stack
+/// instead of recursion, so that nesting depth is bounded by the heap and
+/// not by the call stack.
+///
+/// Children are evaluated left to right and the first error ends the walk,
+/// exactly as a recursive evaluation would.
+pub(crate) struct Walk<'e> {
+    frames: Vec<Frame<'e>>,
+    values: Vec<f64>,
+    env: Cow<'e, HashMap<String, f64>>,
+    config: Cow<'e, EvalConfig>,
+    /// The values bound by the enclosing `let`s, innermost last.
+    bindings: Vec<(&'e str, f64)>,
+}
+
+impl<'e> Walk<'e> {
+    pub(crate) fn new(
+        expr: &'e Expr,
+        env: Cow<'e, HashMap<String, f64>>,
+        config: Cow<'e, EvalConfig>,
+    ) -> Self {
+        Walk {
+            frames: vec![Frame::Visit(expr)],
+            values: Vec::new(),
+            env,
+            config,
+            bindings: Vec::new(),
+        }
+    }
+
+    /// Runs the walk to completion.
+    pub(crate) fn run(mut self, obs: &mut impl EvalObserver) -> Result<f64, EvalError> {
+        loop {
+            if let Some(value) = self.run_frame(obs)? {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// Runs one frame, returning the final value once the walk is done.
+    fn run_frame(&mut self, obs: &mut impl EvalObserver) -> Result<Option<f64>, EvalError> {
+        match self.frames.pop().expect("stepped a finished walk") {
+            Frame::Visit(expr) => {
+                obs.on_enter(expr)?;
+                if let Expr::Let { value, .. } = expr {
+                    self.frames.push(Frame::Bind(expr));
+                    self.frames.push(Frame::Visit(value));
+                } else {
+                    self.frames.push(Frame::Apply(expr));
+                    // Pushed in reverse so that the leftmost child runs first
+                    for child in expr.children().into_iter().rev() {
+                        self.frames.push(Frame::Visit(child));
+                    }
+                }
+                Ok(None)
+            }
+            Frame::Apply(expr) => {
+                let operands = self
+                    .values
+                    .split_off(self.values.len() - expr.children().len());
+                let bound = match expr {
+                    Expr::Var(name) => self.bound(name),
+                    _ => None,
+                };
+                let value = match bound {
+                    Some(value) => value,
+                    None => expr.apply_node(&operands, &self.env, &self.config)?,
+                };
+                Ok(self.finish(expr, value, obs))
+            }
+            Frame::Bind(expr) => {
+                let Expr::Let { name, body, .. } = expr else {
+                    unreachable!("not a let: {:?}", expr);
+                };
+                let value = self.values.pop().expect("the bound value was evaluated");
+                self.bindings.push((name, value));
+                self.frames.push(Frame::Unbind(expr));
+                self.frames.push(Frame::Visit(body));
+                Ok(None)
+            }
+            Frame::Unbind(expr) => {
+                self.bindings.pop();
+                let value = self.values.pop().expect("the body was evaluated");
+                Ok(self.finish(expr, value, obs))
+            }
+        }
+    }
+
+    /// The value bound to `name` by the innermost enclosing `let`, if any.
+    fn bound(&self, name: &str) -> Option<f64> {
+        self.bindings
+            .iter()
+            .rev()
+            .find(|(bound, _)| *bound == name)
+            .map(|&(_, value)| value)
+    }
+
+    fn finish(&mut self, expr: &Expr, value: f64, obs: &mut impl EvalObserver) -> Option<f64> {
+        obs.on_result(expr, value);
+        self.values.push(value);
+        self.frames.is_empty().then_some(value)
+    }
+}
+
+/// An evaluation that can be suspended and resumed, created by
+/// [`Expr::eval_resumable`].
+///
+/// The whole state of a half-finished evaluation lives in the machine
+/// between `step` calls. This lets a scheduler interleave many evaluations
+/// on one thread by giving each a little fuel at a time.
+pub struct EvalMachine<'e> {
+    walk: Walk<'e>,
+    result: Option<Result<f64, EvalError>>,
+}
+
+impl Expr {
+    /// Starts a resumable evaluation of the expression, with the same
+    /// semantics as `eval`.
+    pub fn eval_resumable(&self) -> EvalMachine<'_> {
+        EvalMachine {
+            walk: Walk::new(
+                self,
+                Cow::Owned(HashMap::new()),
+                Cow::Owned(EvalConfig::default()),
+            ),
+            result: None,
+        }
+    }
+}
+
+impl EvalMachine<'_> {
+    /// Runs at most `fuel` units of work, where visiting or combining a
+    /// node costs one unit. Once evaluation has finished, every further
+    /// call returns the same `Done` result.
+    pub fn step(&mut self, fuel: u64) -> StepResult {
+        for _ in 0..fuel {
+            if self.result.is_some() {
+                break;
+            }
+            match self.walk.run_frame(&mut NoopObserver) {
+                Ok(Some(value)) => self.result = Some(Ok(value)),
+                Ok(None) => {}
+                Err(err) => self.result = Some(Err(err)),
+            }
+        }
+        match &self.result {
+            Some(result) => StepResult::Done(result.clone()),
+            None => StepResult::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn parse(input: &str) -> Expr {
+        Parser::new(input).unwrap().parse().unwrap()
+    }
+
+    #[test]
+    fn test_eval_resumable() {
+        let expr = parse("2 + 3 * 4");
+        let mut machine = expr.eval_resumable();
+        let mut pending = 0;
+        let value = loop {
+            match machine.step(2) {
+                StepResult::Pending => pending += 1,
+                StepResult::Done(result) => break result.unwrap(),
+            }
+        };
+        assert_eq!(value, 14.0);
+        assert!(pending >= 3);
+        assert!(matches!(machine.step(1), StepResult::Done(Ok(14.0))));
+
+        // No fuel, no progress
+        let mut machine = expr.eval_resumable();
+        assert!(matches!(machine.step(0), StepResult::Pending));
+        assert!(matches!(machine.step(u64::MAX), StepResult::Done(Ok(14.0))));
+    }
+
+    const DEPTH: usize = 100_000;
+
+    #[test]
+    fn test_eval_deep_nesting() {
+        // Expr::neg folds double negation, so nest the variant directly
+        let mut expr = Expr::number(7.0);
+        for _ in 0..DEPTH {
+            expr = Expr::Neg(Box::new(expr));
+        }
+        assert!(matches!(expr.eval(), Ok(7.0)));
+        expr.drop_iteratively();
+
+        let mut expr = Expr::number(1.0);
+        for _ in 1..DEPTH {
+            expr = Expr::add(expr, Expr::number(1.0));
+        }
+        assert_eq!(expr.eval().unwrap(), DEPTH as f64);
+        let StepResult::Done(result) = expr.eval_resumable().step(u64::MAX) else {
+            panic!("did not finish");
+        };
+        assert_eq!(result.unwrap(), DEPTH as f64);
+
+        // The first error still wins over a deep right operand
+        let expr = Expr::add(Expr::div(Expr::number(1.0), Expr::number(0.0)), expr);
+        assert!(matches!(expr.eval(), Err(EvalError::DivisionByZero { .. })));
+        expr.drop_iteratively();
+    }
+
+    #[test]
+    fn test_eval_resumable_matches_eval() {
+        let inputs = [
+            "1 / (2 - 2)",
+            "max(2, 3) ^ -1",
+            "-(4 - 1) * x",
+            "sqrt(-4)",
+            "let x = 2 in x * (let x = 3 in x) + x",
+            "(let y = 1 in y) + y",
+        ];
+        for input in inputs {
+            let expr = parse(input);
+            let StepResult::Done(result) = expr.eval_resumable().step(u64::MAX) else {
+                panic!("{} did not finish", input);
+            };
+            assert_eq!(
+                format!("{:?}", result),
+                format!("{:?}", expr.eval()),
+                "{}",
+                input
+            );
+        }
+    }
+}
@@ -1,12 +1,15 @@
-use crate::parser::Parser;
-
-mod ast;
-mod error;
-mod lexer;
-mod parser;
-mod token;
+use rust_expr_evaluator::{Parser, batch};
 
 fn main() {
+    if std::env::args().any(|arg| arg == "--batch") {
+        let stdin = std::io::stdin();
+        if let Err(e) = batch::eval_stream(stdin.lock(), std::io::stdout().lock()) {
+            eprintln!("I/O error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let inputs = [
         "2 + 3",
         "2 + 3 * 4",
@@ -25,7 +28,7 @@ fn main() {
         match Parser::new(input) {
             Ok(mut parser) => match parser.parse() {
                 Ok(expr) => match expr.eval() {
-                    Ok(value) => println!("{} = {}", expr.to_string(), value),
+                    Ok(value) => println!("{} = {}", expr, value),
                     Err(e) => println!("Evaluation error: {}", e),
                 },
                 Err(e) => println!("Parse error: {}", e),
@@ -0,0 +1,64 @@
+use std::borrow::Cow;
+
+/// Maps a single character to its ASCII equivalent, if it has one.
+///
+/// The mapping table is:
+///
+/// | Input                                   | Output          |
+/// |-----------------------------------------|-----------------|
+/// | U+FF01..=U+FF5E (full-width ASCII)      | U+0021..=U+007E |
+/// | U+3000 (ideographic space)              | `' '`           |
+/// | U+2212 (minus sign)                     | `'-'`           |
+/// | U+2010, U+2011, U+2012, U+2013 (dashes) | `'-'`           |
+/// | U+00D7 (multiplication sign `×`)        | `'*'`           |
+/// | U+2217 (asterisk operator `∗`)          | `'*'`           |
+/// | U+00F7 (division sign `÷`)              | `'/'`           |
+/// | U+2044 (fraction slash `⁄`)             | `'/'`           |
+/// | U+2018, U+2019 (single smart quotes)    | `'\''`          |
+/// | U+201C, U+201D (double smart quotes)    | `'"'`           |
+///
+/// Every other character is returned unchanged.
+pub fn normalize_char(c: char) -> char {
+    match c {
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFF01 + 0x21).unwrap_or(c),
+        '\u{3000}' => ' ',
+        '\u{2212}' | '\u{2010}' | '\u{2011}' | '\u{2012}' | '\u{2013}' => '-',
+        '\u{00D7}' | '\u{2217}' => '*',
+        '\u{00F7}' | '\u{2044}' => '/',
+        '\u{2018}' | '\u{2019}' => '\'',
+        '\u{201C}' | '\u{201D}' => '"',
+        _ => c,
+    }
+}
+
+/// Normalizes pasted input by applying [`normalize_char`] to every character.
+///
+/// Returns the input unchanged (borrowed) when nothing needed mapping.
+pub fn normalize_input(input: &str) -> Cow<'_, str> {
+    if input.chars().all(|c| normalize_char(c) == c) {
+        Cow::Borrowed(input)
+    } else {
+        Cow::Owned(input.chars().map(normalize_char).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_full_width() {
+        assert_eq!(normalize_input("２　＋　３"), "2 + 3");
+        assert_eq!(normalize_input("（１．５）／２"), "(1.5)/2");
+    }
+
+    #[test]
+    fn test_normalize_lookalikes() {
+        assert_eq!(normalize_input("6 × 2 ÷ 3 − 1"), "6 * 2 / 3 - 1");
+    }
+
+    #[test]
+    fn test_normalize_borrows_ascii() {
+        assert!(matches!(normalize_input("2 + 3"), Cow::Borrowed(_)));
+    }
+}
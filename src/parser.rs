@@ -1,30 +1,80 @@
 use crate::ast::Expr;
 use crate::error::{LexerError, Location, ParseError};
-use crate::lexer::Lexer;
+use crate::lexer::{Lexer, LexerConfig};
 use crate::token::Token;
 
+/// Options controlling how the parser reads its input.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParserConfig {
+    pub lexer: LexerConfig,
+    /// Read a parenthesized bare number, like the `(5)` in `(5) * 2`, as its
+    /// negation, as spreadsheets and accounting ledgers write negatives.
+    ///
+    /// Only parentheses containing exactly one number literal and nothing
+    /// else are affected: `(5)` is -5, but `(2 + 3)`, `(-5)` and `((5))`'s
+    /// outer parentheses are ordinary grouping.
+    pub accounting_negatives: bool,
+}
+
 pub struct Parser<'a> {
     lexer: Lexer<'a>,
+    config: ParserConfig,
     current: Token,
     current_location: Location,
+    consumed: bool,
 }
 
+// range      → binding | expr ('..' expr)?
+// binding    → 'let' IDENTIFIER '=' expr 'in' range
 // expr       → term (('+' | '-') term)*
 // term       → unary (('*' | '/') unary)*
-// unary      → '-' unary | primary
-// primary    → NUMBER | '(' expr ')'
+// unary      → '-' unary | power
+// power      → postfix ('^' unary)?
+// postfix    → primary '!!'*
+// primary    → NUMBER | IDENTIFIER | call | '(' range ')'
+// call       → IDENTIFIER '(' (expr (',' expr)*)? ')'
 
 impl<'a> Parser<'a> {
     pub fn new(input: &'a str) -> Result<Self, LexerError> {
-        let mut lexer = Lexer::new(input);
+        Self::with_lexer_config(input, LexerConfig::default())
+    }
+
+    pub fn with_lexer_config(input: &'a str, config: LexerConfig) -> Result<Self, LexerError> {
+        Self::with_config(
+            input,
+            ParserConfig {
+                lexer: config,
+                ..ParserConfig::default()
+            },
+        )
+    }
+
+    pub fn with_config(input: &'a str, config: ParserConfig) -> Result<Self, LexerError> {
+        let mut lexer = Lexer::with_config(input, config.lexer);
         let (current, current_location) = lexer.next_token()?;
         Ok(Parser {
             lexer,
+            config,
             current,
             current_location,
+            consumed: false,
         })
     }
 
+    /// Re-points the parser at `input` so one `Parser` can be reused for
+    /// many inputs. The lexer configuration is kept.
+    ///
+    /// The parser and lexer hold no heap buffers, so this mainly saves
+    /// rebuilding them and re-threading their configuration for each input.
+    pub fn reset(&mut self, input: &'a str) -> Result<(), LexerError> {
+        self.lexer.reset(input);
+        let (current, current_location) = self.lexer.next_token()?;
+        self.current = current;
+        self.current_location = current_location;
+        self.consumed = false;
+        Ok(())
+    }
+
     fn advance(&mut self) -> Result<Token, ParseError> {
         let prev = self.current.clone();
         let (token, location) = self.lexer.next_token()?;
@@ -33,6 +83,12 @@ impl<'a> Parser<'a> {
         Ok(prev)
     }
 
+    /// Returns the token after `current` without consuming anything.
+    fn peek_token(&self) -> Result<Token, ParseError> {
+        let (token, _) = self.lexer.clone().next_token()?;
+        Ok(token)
+    }
+
     fn check(&self, expected: &Token) -> bool {
         std::mem::discriminant(&self.current) == std::mem::discriminant(expected)
     }
@@ -49,8 +105,22 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Parses the whole input. A `Parser` parses its input once; further
+    /// calls fail until it is [`reset`](Parser::reset) with new input.
     pub fn parse(&mut self) -> Result<Expr, ParseError> {
-        let expr = self.expression()?;
+        if self.consumed {
+            return Err(ParseError::new(
+                "parser already consumed; construct a new Parser",
+                self.current_location,
+            ));
+        }
+        self.consumed = true;
+
+        if self.current == Token::Eof {
+            return Err(ParseError::new("empty expression", self.current_location));
+        }
+        let expr = self.range()?;
+        self.check_missing_operator()?;
         if self.current != Token::Eof {
             return Err(ParseError::new(
                 format!("Expected end of input, got {:?}", self.current),
@@ -60,6 +130,61 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
 
+    /// Reports a targeted error when an operand directly follows a complete
+    /// expression, as in `2 3` or `2 (3)`.
+    ///
+    /// The error is located at the start of the second operand, which is
+    /// where an editor quick-fix would insert the missing operator.
+    fn check_missing_operator(&self) -> Result<(), ParseError> {
+        if matches!(
+            self.current,
+            Token::Number { .. } | Token::Identifier(_) | Token::LeftParen
+        ) {
+            return Err(ParseError::new(
+                "missing operator between operands",
+                self.current_location,
+            ));
+        }
+        Ok(())
+    }
+
+    // range → binding | expr ('..' expr)?
+    //
+    // Ranges bind loosest of all and do not chain: `1..2..3` is an error.
+    fn range(&mut self) -> Result<Expr, ParseError> {
+        if self.current == Token::Let {
+            return self.binding();
+        }
+        let start = self.expression()?;
+        if self.current != Token::DotDot {
+            return Ok(start);
+        }
+        self.advance()?;
+        let end = self.expression()?;
+        Ok(Expr::range(start, end))
+    }
+
+    // binding    → 'let' IDENTIFIER '=' expr 'in' range
+    //
+    // The body extends as far to the right as possible, so a `let` inside
+    // a larger expression needs parentheses: `(let r = 2 in r * r) + 1`.
+    fn binding(&mut self) -> Result<Expr, ParseError> {
+        self.advance()?;
+        let Token::Identifier(name) = self.current.clone() else {
+            return Err(ParseError::new(
+                format!("Expected a name after let, got {:?}", self.current),
+                self.current_location,
+            ));
+        };
+        self.advance()?;
+        self.expect_and_advance(Token::Equals)?;
+        let value = self.expression()?;
+        self.check_missing_operator()?;
+        self.expect_and_advance(Token::In)?;
+        let body = self.range()?;
+        Ok(Expr::let_in(name, value, body))
+    }
+
     // expr → term (('+' | '-') term)*
     fn expression(&mut self) -> Result<Expr, ParseError> {
         let mut left = self.term()?;
@@ -101,26 +226,80 @@ impl<'a> Parser<'a> {
         Ok(unary)
     }
 
-    // unary      → '-' unary | primary
+    // unary      → '-' unary | power
+    //
+    // `Expr::neg` cancels directly nested negations, so a run of `-`s
+    // builds a single `Neg` or none at all: `---5` is `-5` and `----5` is
+    // just `5`, however long the run.
     fn unary(&mut self) -> Result<Expr, ParseError> {
         if self.current == Token::Minus {
             self.advance()?;
             let expr = self.unary()?;
             return Ok(Expr::neg(expr));
         }
-        self.primary()
+        self.power()
     }
 
-    // primary    → NUMBER | '(' expr ')'
+    // power      → postfix ('^' unary)?
+    //
+    // The exponent is parsed by `unary`, which comes back here, so `^` is
+    // right-associative: `2 ^ 3 ^ 2` is `2 ^ (3 ^ 2)`. Since a leading `-`
+    // is consumed by `unary` before the base, `-2 ^ 2` is `-(2 ^ 2)`, while
+    // `2 ^ -1` negates just the exponent.
+    fn power(&mut self) -> Result<Expr, ParseError> {
+        let base = self.postfix()?;
+        if self.current != Token::Caret {
+            return Ok(base);
+        }
+        self.advance()?;
+        let exponent = self.unary()?;
+        Ok(Expr::pow(base, exponent))
+    }
+
+    // postfix    → primary '!!'*
+    //
+    // `!!` is the double factorial, binding tighter than any other
+    // operator: `-3!!` is `-(3!!)` and `2 ^ 3!!` is `2 ^ (3!!)`. Repeated
+    // `!!`s apply in turn, so `5!!!!` is `(5!!)!!`; it is never read as a
+    // factorial of a factorial, `(5!)!`, since there is no single `!`.
+    fn postfix(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.primary()?;
+        while self.current == Token::DoubleBang {
+            self.advance()?;
+            expr = Expr::double_factorial(expr);
+        }
+        Ok(expr)
+    }
+
+    // primary    → NUMBER | IDENTIFIER | call | '(' range ')'
     fn primary(&mut self) -> Result<Expr, ParseError> {
         match self.current {
-            Token::Number(n) => {
+            Token::Number { value: n, .. } => {
                 self.advance()?;
                 Ok(Expr::number(n))
             }
+            Token::Identifier(ref name) => {
+                let name = name.clone();
+                let call_paren = self.current_location.advance_column(name.len());
+                self.advance()?;
+                if self.current == Token::LeftParen && self.current_location == call_paren {
+                    self.call(name)
+                } else {
+                    Ok(Expr::Var(name))
+                }
+            }
             Token::LeftParen => {
                 self.advance()?;
-                let expr = self.expression()?;
+                if self.config.accounting_negatives
+                    && let Token::Number { value: n, .. } = self.current
+                    && self.peek_token()? == Token::RightParen
+                {
+                    self.advance()?;
+                    self.advance()?;
+                    return Ok(Expr::neg(Expr::number(n)));
+                }
+                let expr = self.range()?;
+                self.check_missing_operator()?;
                 self.expect_and_advance(Token::RightParen)?;
                 Ok(expr)
             }
@@ -130,11 +309,94 @@ impl<'a> Parser<'a> {
             )),
         }
     }
+
+    // call       → IDENTIFIER '(' (expr (',' expr)*)? ')'
+    //
+    // Only a `(` directly after the name starts a call; `f (x)` is the
+    // variable `f` followed by a stray operand.
+    fn call(&mut self, name: String) -> Result<Expr, ParseError> {
+        self.advance()?;
+        let mut args = Vec::new();
+        if self.current != Token::RightParen {
+            loop {
+                args.push(self.expression()?);
+                self.check_missing_operator()?;
+                if self.current != Token::Comma {
+                    break;
+                }
+                self.advance()?;
+            }
+        }
+        self.expect_and_advance(Token::RightParen)?;
+        Ok(Expr::call(name, args))
+    }
+}
+
+/// Parses `input`, distinguishing empty input from invalid input: empty or
+/// whitespace-only input is `Ok(None)`.
+pub fn parse_optional(input: &str) -> Result<Option<Expr>, ParseError> {
+    let mut parser = Parser::new(input)?;
+    if parser.current == Token::Eof {
+        return Ok(None);
+    }
+    parser.parse().map(Some)
+}
+
+/// Parses `input` as a single number literal with an optional leading `+`
+/// or `-` directly before its first digit, as for validating a numeric
+/// form field. Anything else is an error, so `-3.5` is accepted but
+/// `2 + 3`, `--3`, `- 3`, `(3)` and `1e400`, which is too large for an
+/// `f64`, are not.
+pub fn parse_number(input: &str) -> Result<f64, ParseError> {
+    let mut parser = Parser::new(input)?;
+    let sign_location = parser.current_location;
+    let signed = matches!(parser.current, Token::Minus | Token::Plus);
+    let sign = match parser.current {
+        Token::Minus => {
+            parser.advance()?;
+            -1.0
+        }
+        Token::Plus => {
+            parser.advance()?;
+            1.0
+        }
+        _ => 1.0,
+    };
+    let Token::Number { value, .. } = parser.current else {
+        return Err(ParseError::new(
+            format!("Expected a number, got {:?}", parser.current),
+            parser.current_location,
+        ));
+    };
+    if signed && parser.current_location != sign_location.advance_column(1) {
+        return Err(ParseError::new(
+            "Expected the number directly after its sign",
+            parser.current_location,
+        ));
+    }
+    if value.is_infinite() {
+        return Err(ParseError::new(
+            "Number is too large for a 64-bit float",
+            parser.current_location,
+        ));
+    }
+    parser.advance()?;
+    if parser.current != Token::Eof {
+        return Err(ParseError::new(
+            format!(
+                "Expected end of input after number, got {:?}",
+                parser.current
+            ),
+            parser.current_location,
+        ));
+    }
+    Ok(sign * value)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::EvalError;
 
     #[test]
     fn test_parser() {
@@ -162,6 +424,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parser_folds_negations() {
+        let mut parser = Parser::new("--5").expect("Failed to create parser");
+        assert_eq!(parser.parse().unwrap(), Expr::Number(5.0));
+
+        let mut parser = Parser::new("---5").expect("Failed to create parser");
+        assert_eq!(
+            parser.parse().unwrap(),
+            Expr::Neg(Box::new(Expr::Number(5.0)))
+        );
+    }
+
     #[test]
     fn test_parser_error_location() {
         // "2 + + 3" - error at second '+' which is at column 5
@@ -186,10 +460,377 @@ mod tests {
         assert!(err.message.contains("Expected RightParen"));
     }
 
+    #[test]
+    fn test_parser_normalized_input() {
+        let config = LexerConfig {
+            normalize_input: true,
+            ..LexerConfig::default()
+        };
+        let mut parser =
+            Parser::with_lexer_config("２ ＋ ３", config).expect("Failed to create parser");
+        assert_eq!(parser.parse().unwrap().eval().unwrap(), 5.0);
+
+        assert!(Parser::new("２ ＋ ３").is_err());
+    }
+
+    #[test]
+    fn test_parser_reset() {
+        let mut parser = Parser::new("1 + 1").expect("Failed to create parser");
+        assert_eq!(parser.parse().unwrap().eval().unwrap(), 2.0);
+
+        for (input, expected) in [("2 * 3", 6.0), ("(1 + 2) * 4", 12.0), ("--7", 7.0)] {
+            parser.reset(input).expect("Failed to reset parser");
+            assert_eq!(parser.parse().unwrap().eval().unwrap(), expected);
+        }
+
+        // An error on one input does not poison the next
+        parser.reset("(2 + 3").expect("Failed to reset parser");
+        assert!(parser.parse().is_err());
+        parser.reset("4 / 2").expect("Failed to reset parser");
+        assert_eq!(parser.parse().unwrap().eval().unwrap(), 2.0);
+    }
+
+    #[test]
+    fn test_parser_missing_operator() {
+        for input in ["2 3", "2 (3)", "(2 3)"] {
+            let mut parser = Parser::new(input).expect("Failed to create parser");
+            let err = parser.parse().unwrap_err();
+            assert_eq!(err.message, "missing operator between operands");
+        }
+    }
+
+    #[test]
+    fn test_parser_parse_twice() {
+        let mut parser = Parser::new("2 + 3").expect("Failed to create parser");
+        assert!(parser.parse().is_ok());
+        let err = parser.parse().unwrap_err();
+        assert_eq!(
+            err.message,
+            "parser already consumed; construct a new Parser"
+        );
+    }
+
+    #[test]
+    fn test_parser_accounting_negatives() {
+        let config = ParserConfig {
+            accounting_negatives: true,
+            ..ParserConfig::default()
+        };
+        let eval = |input| {
+            let mut parser = Parser::with_config(input, config).expect("Failed to create parser");
+            parser.parse().unwrap().eval().unwrap()
+        };
+        assert_eq!(eval("(5)"), -5.0);
+        assert_eq!(eval("(5) * 2"), -10.0);
+        assert_eq!(eval("(2+3)*4"), 20.0);
+        assert_eq!(eval("((5))"), -5.0);
+        assert_eq!(eval("(-5)"), -5.0);
+
+        let mut parser = Parser::new("(5)").expect("Failed to create parser");
+        assert_eq!(parser.parse().unwrap().eval().unwrap(), 5.0);
+    }
+
+    #[test]
+    fn test_parser_bom() {
+        let mut parser = Parser::new("\u{FEFF}2 + 3").expect("Failed to create parser");
+        assert_eq!(parser.parse().unwrap().eval().unwrap(), 5.0);
+    }
+
+    #[test]
+    fn test_parse_optional() {
+        assert_eq!(parse_optional("").unwrap(), None);
+        assert_eq!(parse_optional(" \t\n").unwrap(), None);
+        assert_eq!(
+            parse_optional("1 + 2").unwrap(),
+            Some(Expr::add(Expr::number(1.0), Expr::number(2.0)))
+        );
+        assert!(parse_optional("1 +").is_err());
+    }
+
+    #[test]
+    fn test_parser_comments() {
+        let config = LexerConfig {
+            comments: true,
+            ..LexerConfig::default()
+        };
+        let eval = |input| {
+            let mut parser =
+                Parser::with_lexer_config(input, config).expect("Failed to create parser");
+            parser.parse().unwrap().eval().unwrap()
+        };
+        assert_eq!(eval("#!/usr/bin/env expr-eval\n2+3"), 5.0);
+        assert_eq!(eval("# header\n2 * # inline\n(3 + 1)"), 8.0);
+
+        assert!(Parser::new("#!/usr/bin/env expr-eval\n2+3").is_err());
+    }
+
+    #[test]
+    fn test_parser_trailing_comment() {
+        let config = LexerConfig {
+            comments: true,
+            ..LexerConfig::default()
+        };
+        let mut parser = Parser::with_lexer_config("2 + 3 // note", config).unwrap();
+        assert_eq!(parser.parse().unwrap().eval().unwrap(), 5.0);
+
+        let mut parser = Parser::with_lexer_config("8 / 2 # half", config).unwrap();
+        assert_eq!(parser.parse().unwrap().eval().unwrap(), 4.0);
+
+        for input in ["// only a comment", "# only a comment\n"] {
+            let mut parser = Parser::with_lexer_config(input, config).unwrap();
+            assert_eq!(parser.parse().unwrap_err().message, "empty expression");
+        }
+    }
+
+    #[test]
+    fn test_parser_empty_input() {
+        let mut parser = Parser::new("   ").expect("Failed to create parser");
+        assert_eq!(parser.parse().unwrap_err().message, "empty expression");
+    }
+
+    #[test]
+    fn test_parser_missing_operator_location() {
+        let mut parser = Parser::new("2 3").expect("Failed to create parser");
+        let err = parser.parse().unwrap_err();
+        assert_eq!(err.location, Location::new(1, 3));
+
+        let mut parser = Parser::new("(1 + 2)\n  (3)").expect("Failed to create parser");
+        let err = parser.parse().unwrap_err();
+        assert_eq!(err.location, Location::new(2, 3));
+    }
+
     #[test]
     fn test_parser_lexer_error() {
         let mut parser = Parser::new("2 + @").expect("Failed to create parser");
         let err = parser.parse();
         assert!(err.is_err());
     }
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number("-3.25").unwrap(), -3.25);
+        assert_eq!(parse_number("-3.14e2").unwrap(), -314.0);
+        assert_eq!(parse_number(" +42 ").unwrap(), 42.0);
+        assert_eq!(parse_number(".5").unwrap(), 0.5);
+
+        let err = parse_number("2+3").unwrap_err();
+        assert_eq!(err.message, "Expected end of input after number, got Plus");
+        assert_eq!(err.location, Location::new(1, 2));
+
+        let err = parse_number("--3").unwrap_err();
+        assert_eq!(err.message, "Expected a number, got Minus");
+        assert_eq!(err.location, Location::new(1, 2));
+
+        let err = parse_number("- 3").unwrap_err();
+        assert_eq!(err.message, "Expected the number directly after its sign");
+        assert_eq!(err.location, Location::new(1, 3));
+        assert!(parse_number("+\n3").is_err());
+
+        let err = parse_number("1e400").unwrap_err();
+        assert_eq!(err.message, "Number is too large for a 64-bit float");
+        assert!(parse_number("-1e400").is_err());
+
+        assert!(parse_number("").is_err());
+        assert!(parse_number("(3)").is_err());
+        assert!(parse_number("3@").is_err());
+    }
+
+    #[test]
+    fn test_parser_range() {
+        let expr = Parser::new("0..10").unwrap().parse().unwrap();
+        assert_eq!(expr, Expr::range(Expr::number(0.0), Expr::number(10.0)));
+        assert_eq!(expr.eval_range().unwrap(), (0.0, 10.0));
+        assert!(matches!(expr.eval(), Err(EvalError::NotAScalar)));
+
+        // Ranges bind looser than arithmetic
+        let expr = Parser::new("1 + 1..2 * 5").unwrap().parse().unwrap();
+        assert_eq!(expr.eval_range().unwrap(), (2.0, 10.0));
+
+        let expr = Parser::new("1.5").unwrap().parse().unwrap();
+        assert_eq!(expr, Expr::number(1.5));
+        assert_eq!(expr.eval_range().unwrap(), (1.5, 1.5));
+
+        let err = Parser::new("1..2..3").unwrap().parse().unwrap_err();
+        assert_eq!(err.message, "Expected end of input, got DotDot");
+        assert_eq!(err.location, Location::new(1, 5));
+    }
+
+    #[test]
+    fn test_parser_unary_runs() {
+        let parse = |input| Parser::new(input).unwrap().parse().unwrap();
+
+        assert_eq!(parse("----5"), Expr::Number(5.0));
+        assert_eq!(parse("---5"), Expr::Neg(Box::new(Expr::Number(5.0))));
+        assert_eq!(parse("- - - 5"), parse("-5"));
+        assert_eq!(parse("-(-(x))"), Expr::var("x"));
+        assert_eq!(parse(&format!("{}2", "-".repeat(1001))).depth(), 2);
+    }
+
+    #[test]
+    fn test_parser_double_factorial() {
+        let eval = |input| Parser::new(input).unwrap().parse().unwrap().eval();
+
+        assert_eq!(eval("5!!").unwrap(), 15.0);
+        assert_eq!(eval("6!!").unwrap(), 48.0);
+        assert_eq!(eval("0!!").unwrap(), 1.0);
+        // Stacked `!!`s apply in turn: (5!!)!! = 15!!
+        assert_eq!(eval("5!!!!").unwrap(), 2027025.0);
+        assert_eq!(eval("-3!!").unwrap(), -3.0);
+        assert_eq!(eval("2 ^ 3!!").unwrap(), 8.0);
+        assert_eq!(eval("(1 + 3)!!").unwrap(), 8.0);
+
+        assert!(matches!(eval("(-1)!!"), Err(EvalError::DomainError(_))));
+        assert!(matches!(eval("2.5!!"), Err(EvalError::DomainError(_))));
+        assert!(matches!(eval("400!!"), Err(EvalError::Overflow { .. })));
+        assert!(matches!(eval("1e300!!"), Err(EvalError::Overflow { .. })));
+    }
+
+    #[test]
+    fn test_parser_power() {
+        let parse = |input| Parser::new(input).unwrap().parse().unwrap();
+        let n = Expr::number;
+
+        assert_eq!(parse("2 ^ 3"), Expr::pow(n(2.0), n(3.0)));
+        assert_eq!(parse("2 ^ 3").eval().unwrap(), 8.0);
+
+        let expr = parse("2 ^ 3 ^ 2");
+        assert_eq!(expr, Expr::pow(n(2.0), Expr::pow(n(3.0), n(2.0))));
+        assert_eq!(expr.eval().unwrap(), 512.0);
+
+        assert_eq!(parse("(1 + 1) ^ 3").eval().unwrap(), 8.0);
+        assert_eq!(parse("2 * 3 ^ 2").eval().unwrap(), 18.0);
+
+        let expr = parse("-2 ^ 2");
+        assert_eq!(expr, Expr::neg(Expr::pow(n(2.0), n(2.0))));
+        assert_eq!(expr.eval().unwrap(), -4.0);
+        assert_eq!(parse("2 ^ -1").eval().unwrap(), 0.5);
+
+        let err = Parser::new("2 ^").unwrap().parse().unwrap_err();
+        assert_eq!(err.message, "Expected expression, got Eof");
+        assert_eq!(err.location, Location::new(1, 4));
+    }
+
+    #[test]
+    fn test_parser_variables() {
+        use std::collections::HashMap;
+
+        let env = HashMap::from([("x".to_string(), 3.0), ("y".to_string(), 4.0)]);
+
+        let expr = Parser::new("x").unwrap().parse().unwrap();
+        assert_eq!(expr, Expr::var("x"));
+        assert_eq!(expr.eval_with(&env).unwrap(), 3.0);
+
+        let expr = Parser::new("x * x + 2 * y").unwrap().parse().unwrap();
+        assert_eq!(expr.eval_with(&env).unwrap(), 17.0);
+
+        let expr = Parser::new("x + z").unwrap().parse().unwrap();
+        match expr.eval_with(&env) {
+            Err(EvalError::UndefinedVariable(name)) => assert_eq!(name, "z"),
+            other => panic!("expected an undefined variable, got {:?}", other),
+        }
+        assert!(matches!(expr.eval(), Err(EvalError::UndefinedVariable(_))));
+
+        let expr = Parser::new("rate_1 * -x").unwrap().parse().unwrap();
+        let printed = expr.to_string();
+        assert!(printed.contains("rate_1"));
+        assert_eq!(Parser::new(&printed).unwrap().parse().unwrap(), expr);
+
+        let err = Parser::new("2 x").unwrap().parse().unwrap_err();
+        assert_eq!(err.message, "missing operator between operands");
+    }
+
+    #[test]
+    fn test_parser_let() {
+        use std::collections::HashMap;
+
+        let parse = |input| Parser::new(input).unwrap().parse();
+
+        let expr = parse("let r = 5 in r * r").unwrap();
+        assert_eq!(
+            expr,
+            Expr::let_in(
+                "r",
+                Expr::number(5.0),
+                Expr::mul(Expr::var("r"), Expr::var("r"))
+            )
+        );
+        assert_eq!(expr.eval().unwrap(), 25.0);
+
+        // The binding is not visible outside its body
+        let expr = parse("(let r = 5 in r) + r").unwrap();
+        match expr.eval() {
+            Err(EvalError::UndefinedVariable(name)) => assert_eq!(name, "r"),
+            other => panic!("expected an undefined variable, got {:?}", other),
+        }
+        let env = HashMap::from([("r".to_string(), 1.0)]);
+        assert_eq!(expr.eval_with(&env).unwrap(), 6.0);
+
+        // Inner bindings shadow outer ones, and values see the outer scope
+        let expr = parse("let x = 2 in let x = x + 1 in x * 10").unwrap();
+        assert_eq!(expr.eval().unwrap(), 30.0);
+        let expr = parse("let n = 3 in 0..n").unwrap();
+        assert_eq!(expr.eval_range().unwrap(), (0.0, 3.0));
+
+        for (input, message) in [
+            ("let 5 = 1 in 2", "Expected a name after let, got Number"),
+            ("let x 1 in x", "Expected Equals"),
+            ("let x = 1 x", "missing operator between operands"),
+            ("let x = 1", "Expected In, got Eof"),
+            ("1 + let x = 1 in x", "Expected expression, got Let"),
+        ] {
+            let err = parse(input).unwrap_err();
+            assert!(
+                err.message.starts_with(message),
+                "{}: {}",
+                input,
+                err.message
+            );
+        }
+    }
+
+    #[test]
+    fn test_parser_functions() {
+        let eval = |input| Parser::new(input).unwrap().parse().unwrap().eval();
+
+        assert_eq!(eval("sqrt(16)").unwrap(), 4.0);
+        assert_eq!(eval("sqrt(sqrt(16))").unwrap(), 2.0);
+        assert_eq!(eval("abs(-3)").unwrap(), 3.0);
+        assert_eq!(eval("sin(0) + cos(0)").unwrap(), 1.0);
+        assert_eq!(eval("max(2, 3 * 4) - min(1, -1)").unwrap(), 13.0);
+
+        assert!(matches!(eval("sqrt(-1)"), Err(EvalError::DomainError(_))));
+        assert_eq!(
+            eval("asin(2)").unwrap_err().to_string(),
+            "Domain error: asin of 2, which is outside [-1, 1]"
+        );
+        assert_eq!(
+            eval("ln(0 - 1)").unwrap_err().to_string(),
+            "Domain error: ln of -1, which is not positive"
+        );
+        assert!(matches!(
+            eval("cbrt(8)"),
+            Err(EvalError::UnknownFunction(name)) if name == "cbrt"
+        ));
+        assert!(matches!(
+            eval("max(1)"),
+            Err(EvalError::ArityMismatch {
+                expected: 2,
+                found: 1,
+                ..
+            })
+        ));
+
+        let expr = Parser::new("2 * max(x, -1) ^ 2").unwrap().parse().unwrap();
+        assert_eq!(expr.to_string(), "2 * max(x, -1) ^ 2");
+        assert_eq!(
+            Parser::new(&expr.to_string()).unwrap().parse().unwrap(),
+            expr
+        );
+
+        // A call needs its parenthesis right after the name
+        let err = Parser::new("sqrt (16)").unwrap().parse().unwrap_err();
+        assert_eq!(err.message, "missing operator between operands");
+        let err = Parser::new("max(1, 2").unwrap().parse().unwrap_err();
+        assert_eq!(err.message, "Expected RightParen, got Eof");
+    }
 }
@@ -0,0 +1,101 @@
+use crate::ast::Expr;
+
+/// Limits on the literals an expression may contain, checked by
+/// [`Expr::check_literals`]. `None` leaves that aspect unchecked.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LiteralPolicy {
+    /// Most digits allowed after the decimal point.
+    pub max_decimals: Option<usize>,
+    /// Largest magnitude allowed.
+    pub max_abs: Option<f64>,
+}
+
+/// A literal rejected by a [`LiteralPolicy`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum LiteralViolation {
+    TooManyDecimals { value: f64, decimals: usize },
+    TooLarge { value: f64 },
+}
+
+impl Expr {
+    /// Checks every number literal against `policy`, returning all
+    /// violations in left-to-right order.
+    ///
+    /// The tree does not keep the source text, so decimals are counted on
+    /// the shortest representation that round-trips the parsed `f64`:
+    /// `1.50` counts as one decimal, and `0.1` as one rather than the
+    /// fifty-odd digits of its exact binary value.
+    pub fn check_literals(&self, policy: &LiteralPolicy) -> Result<(), Vec<LiteralViolation>> {
+        let mut violations = Vec::new();
+        for value in self.collect_constants() {
+            if let Some(max) = policy.max_decimals {
+                let decimals = decimal_places(value);
+                if decimals > max {
+                    violations.push(LiteralViolation::TooManyDecimals { value, decimals });
+                }
+            }
+            if let Some(max) = policy.max_abs
+                && value.abs() > max
+            {
+                violations.push(LiteralViolation::TooLarge { value });
+            }
+        }
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+fn decimal_places(value: f64) -> usize {
+    let text = value.to_string();
+    text.split_once('.')
+        .map_or(0, |(_, fraction)| fraction.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn n(value: f64) -> Expr {
+        Expr::number(value)
+    }
+
+    #[test]
+    fn test_check_literals_decimals() {
+        let policy = LiteralPolicy {
+            max_decimals: Some(2),
+            ..LiteralPolicy::default()
+        };
+        let expr = Expr::add(n(1.23456), Expr::mul(n(0.5), n(1.239)));
+        assert_eq!(
+            expr.check_literals(&policy),
+            Err(vec![
+                LiteralViolation::TooManyDecimals {
+                    value: 1.23456,
+                    decimals: 5
+                },
+                LiteralViolation::TooManyDecimals {
+                    value: 1.239,
+                    decimals: 3
+                },
+            ])
+        );
+        assert_eq!(Expr::sub(n(0.1), n(12.25)).check_literals(&policy), Ok(()));
+    }
+
+    #[test]
+    fn test_check_literals_magnitude() {
+        let policy = LiteralPolicy {
+            max_abs: Some(1000.0),
+            ..LiteralPolicy::default()
+        };
+        let expr = Expr::sub(Expr::neg(n(5000.0)), n(1000.0));
+        assert_eq!(
+            expr.check_literals(&policy),
+            Err(vec![LiteralViolation::TooLarge { value: 5000.0 }])
+        );
+        assert_eq!(n(1e300).check_literals(&LiteralPolicy::default()), Ok(()));
+    }
+}
@@ -0,0 +1,466 @@
+use std::fmt;
+
+use crate::ast::Expr;
+
+/// The precedence of `+` and `-`, the loosest operators allowed in a
+/// function argument or a `let`'s value.
+const ADDITIVE: u8 = 2;
+
+/// How parentheses are placed when rendering an expression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParenStyle {
+    /// Only where precedence or associativity requires them, so the output
+    /// parses back to the same tree.
+    Minimal,
+    /// Around every operand.
+    Full,
+    /// Never. The output is for display only and may not parse back to
+    /// the same tree.
+    None,
+}
+
+/// Options for [`Expr::to_infix_with_config`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrintConfig {
+    pub parens: ParenStyle,
+    /// Put a space on either side of binary operators.
+    pub spaces: bool,
+}
+
+impl Default for PrintConfig {
+    fn default() -> Self {
+        PrintConfig {
+            parens: ParenStyle::Full,
+            spaces: true,
+        }
+    }
+}
+
+/// Renders the expression in infix notation with as few parentheses as
+/// round-tripping through the parser allows, so `2 + 3 * 4` prints as
+/// written and `(2 + 3) * 4` keeps its parentheses.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cfg = PrintConfig {
+            parens: ParenStyle::Minimal,
+            spaces: true,
+        };
+        f.write_str(&self.to_infix_with_config(&cfg))
+    }
+}
+
+impl Expr {
+    /// Renders the expression in infix notation according to `cfg`.
+    pub fn to_infix_with_config(&self, cfg: &PrintConfig) -> String {
+        let mut out = String::new();
+        self.write_infix(cfg, &mut out);
+        out
+    }
+
+    /// Renders the tree structure with one node per line, e.g. for
+    /// `2 + 3 * 4`:
+    ///
+    /// ```text
+    /// Add
+    /// ├─ 2
+    /// └─ Mul
+    ///    ├─ 3
+    ///    └─ 4
+    /// ```
+    pub fn pretty_print_tree(&self) -> String {
+        let mut out = self.tree_label();
+        out.push('\n');
+        self.write_tree_children("", &mut out);
+        out
+    }
+
+    fn tree_label(&self) -> String {
+        match self {
+            Expr::Number(n) => n.to_string(),
+            Expr::Var(name) => name.clone(),
+            Expr::Call(name, _) => format!("{}()", name),
+            Expr::Add(..) => "Add".to_string(),
+            Expr::Sub(..) => "Sub".to_string(),
+            Expr::Mul(..) => "Mul".to_string(),
+            Expr::Div(..) => "Div".to_string(),
+            Expr::Pow(..) => "Pow".to_string(),
+            Expr::Range(..) => "Range".to_string(),
+            Expr::Neg(_) => "Neg".to_string(),
+            Expr::DoubleFactorial(_) => "DoubleFactorial".to_string(),
+            Expr::Let { name, .. } => format!("Let {}", name),
+        }
+    }
+
+    fn write_tree_children(&self, prefix: &str, out: &mut String) {
+        let children = self.children();
+        let last = children.len().saturating_sub(1);
+        for (i, child) in children.into_iter().enumerate() {
+            let (connector, indent) = if i == last {
+                ("└─ ", "   ")
+            } else {
+                ("├─ ", "│  ")
+            };
+            out.push_str(prefix);
+            out.push_str(connector);
+            out.push_str(&child.tree_label());
+            out.push('\n');
+            child.write_tree_children(&format!("{}{}", prefix, indent), out);
+        }
+    }
+
+    /// Binding strength used to decide where `ParenStyle::Minimal` needs
+    /// parentheses. Higher binds tighter.
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Let { .. } => 0,
+            Expr::Range(..) => 1,
+            Expr::Add(..) | Expr::Sub(..) => ADDITIVE,
+            Expr::Mul(..) | Expr::Div(..) => 3,
+            Expr::Neg(_) => 4,
+            Expr::Number(n) if n.is_sign_negative() => 4,
+            Expr::Pow(..) => 5,
+            Expr::DoubleFactorial(_) => 6,
+            Expr::Number(_) | Expr::Var(_) | Expr::Call(..) => 7,
+        }
+    }
+
+    fn write_infix(&self, cfg: &PrintConfig, out: &mut String) {
+        let prec = self.precedence();
+        match self {
+            Expr::Number(n) => out.push_str(&n.to_string()),
+            Expr::Var(name) => out.push_str(name),
+            Expr::Call(name, args) => {
+                // The argument list delimits each argument, so only the
+                // ranges and bindings the grammar excludes from arguments
+                // need parentheses of their own.
+                out.push_str(name);
+                out.push('(');
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(if cfg.spaces { ", " } else { "," });
+                    }
+                    if arg.precedence() < ADDITIVE && cfg.parens != ParenStyle::None {
+                        out.push('(');
+                        arg.write_infix(cfg, out);
+                        out.push(')');
+                    } else {
+                        arg.write_infix(cfg, out);
+                    }
+                }
+                out.push(')');
+            }
+            Expr::Neg(a) => {
+                out.push('-');
+                a.write_operand(cfg, a.precedence() < prec, out);
+            }
+            Expr::DoubleFactorial(a) => {
+                a.write_operand(cfg, a.precedence() < prec, out);
+                out.push_str("!!");
+            }
+            Expr::Add(a, b) => Self::write_binary(a, "+", b, prec, false, cfg, out),
+            Expr::Sub(a, b) => Self::write_binary(a, "-", b, prec, false, cfg, out),
+            Expr::Mul(a, b) => Self::write_binary(a, "*", b, prec, false, cfg, out),
+            Expr::Div(a, b) => Self::write_binary(a, "/", b, prec, false, cfg, out),
+            Expr::Pow(a, b) => Self::write_binary(a, "^", b, prec, true, cfg, out),
+            Expr::Range(a, b) => {
+                // Ranges do not chain, so a range on either side needs
+                // parentheses
+                let left_parens = a.precedence() <= prec;
+                let right_parens = b.precedence() <= prec;
+                Self::write_infix_op(a, left_parens, "..", b, right_parens, cfg, out);
+            }
+            Expr::Let { name, value, body } => {
+                out.push_str("let ");
+                out.push_str(name);
+                out.push_str(if cfg.spaces { " = " } else { "=" });
+                value.write_operand(cfg, value.precedence() < ADDITIVE, out);
+                out.push_str(" in ");
+                body.write_operand(cfg, false, out);
+            }
+        }
+    }
+
+    fn write_binary(
+        left: &Expr,
+        op: &str,
+        right: &Expr,
+        prec: u8,
+        right_assoc: bool,
+        cfg: &PrintConfig,
+        out: &mut String,
+    ) {
+        // An operand at the same precedence only needs parentheses on the
+        // side the operator does not associate towards.
+        let left_parens = left.precedence() < prec || (right_assoc && left.precedence() == prec);
+        let right_parens =
+            right.precedence() < prec || (!right_assoc && right.precedence() == prec);
+        Self::write_infix_op(left, left_parens, op, right, right_parens, cfg, out);
+    }
+
+    fn write_infix_op(
+        left: &Expr,
+        left_parens: bool,
+        op: &str,
+        right: &Expr,
+        right_parens: bool,
+        cfg: &PrintConfig,
+        out: &mut String,
+    ) {
+        left.write_operand(cfg, left_parens, out);
+        if cfg.spaces {
+            out.push(' ');
+            out.push_str(op);
+            out.push(' ');
+        } else {
+            out.push_str(op);
+        }
+        right.write_operand(cfg, right_parens, out);
+    }
+
+    fn write_operand(&self, cfg: &PrintConfig, needed: bool, out: &mut String) {
+        let parens = match cfg.parens {
+            ParenStyle::Minimal => needed,
+            ParenStyle::Full => true,
+            ParenStyle::None => false,
+        };
+        if parens {
+            out.push('(');
+            self.write_infix(cfg, out);
+            out.push(')');
+        } else {
+            self.write_infix(cfg, out);
+        }
+    }
+}
+
+/// The number of significant digits [`format_result`] shows.
+const RESULT_DIGITS: i32 = 15;
+
+/// Formats a result the way a calculator display shows it. The output is
+/// that of C's `printf("%.15g", value)`, except that `-0.0` shows as `0`:
+///
+/// - the value is rounded to 15 significant digits and trailing zeros are
+///   trimmed, so `2.0` shows as `2` and `1.0 / 3.0` as `0.333333333333333`;
+/// - values whose decimal exponent, after rounding, is below -4 or at
+///   least 15 use scientific notation with a signed exponent of at least
+///   two digits, e.g. `1e+15` and `1.5e-07`;
+/// - infinities show as `inf` and `-inf`, and NaN as `nan`.
+pub fn format_result(value: f64) -> String {
+    if value.is_nan() {
+        return "nan".to_string();
+    }
+    if value.is_infinite() {
+        return if value > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+    if value == 0.0 {
+        return "0".to_string();
+    }
+    // Round once in scientific form; the exponent then accounts for any
+    // carry, e.g. 9.9999999999999999 rounding up to 1e1.
+    let scientific = format!("{:.*e}", (RESULT_DIGITS - 1) as usize, value);
+    let (mantissa, exponent) = scientific
+        .split_once('e')
+        .expect("scientific notation has an exponent");
+    let exponent: i32 = exponent.parse().expect("the exponent is an integer");
+    if (-4..RESULT_DIGITS).contains(&exponent) {
+        let decimals = (RESULT_DIGITS - 1 - exponent) as usize;
+        trim_zeros(&format!("{:.*}", decimals, value)).to_string()
+    } else {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", trim_zeros(mantissa), sign, exponent.abs())
+    }
+}
+
+/// Drops trailing zeros after a decimal point, and the point itself if
+/// nothing follows it.
+fn trim_zeros(digits: &str) -> &str {
+    if digits.contains('.') {
+        digits.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        digits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(parens: ParenStyle, spaces: bool) -> PrintConfig {
+        PrintConfig { parens, spaces }
+    }
+
+    #[test]
+    fn test_paren_styles() {
+        // (2 + 3) * 4
+        let expr = Expr::mul(
+            Expr::add(Expr::number(2.0), Expr::number(3.0)),
+            Expr::number(4.0),
+        );
+        assert_eq!(
+            expr.to_infix_with_config(&config(ParenStyle::Full, true)),
+            "((2) + (3)) * (4)"
+        );
+        assert_eq!(
+            expr.to_infix_with_config(&config(ParenStyle::Minimal, true)),
+            "(2 + 3) * 4"
+        );
+        assert_eq!(
+            expr.to_infix_with_config(&config(ParenStyle::None, true)),
+            "2 + 3 * 4"
+        );
+    }
+
+    #[test]
+    fn test_full_style() {
+        let expr = Expr::sub(
+            Expr::neg(Expr::number(1.0)),
+            Expr::div(Expr::number(2.0), Expr::number(3.0)),
+        );
+        assert_eq!(
+            expr.to_infix_with_config(&PrintConfig::default()),
+            "(-(1)) - ((2) / (3))"
+        );
+        assert_eq!(
+            expr.to_infix_with_config(&config(ParenStyle::Full, false)),
+            "(-(1))-((2)/(3))"
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        fn parse(input: &str) -> Expr {
+            crate::parser::Parser::new(input).unwrap().parse().unwrap()
+        }
+        for input in [
+            "2 + 3 * 4",
+            "(2 + 3) * 4",
+            "10 - (2 - 3)",
+            "10 - 2 - 3",
+            "8 / (4 / 2)",
+            "8 / 4 * 2",
+            "8 / (4 * 2)",
+            "-5",
+            "-(2 + 3) * 4",
+            "2 ^ 3 ^ 2",
+            "(2 ^ 3) ^ 2",
+            "(-2) ^ 2",
+            "-2 ^ 2",
+            "1 - -x",
+            "max(1 + 2, -abs(x))",
+            "(1 + 2)!!",
+            "0 .. n + 1",
+            "(0 .. 1) .. 2",
+            "0 .. (1 .. 2)",
+            "let r = 2 in r * r",
+        ] {
+            let expr = parse(input);
+            assert_eq!(format!("{}", expr), input);
+            assert_eq!(parse(&expr.to_string()), expr, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_pretty_print_tree() {
+        // 2 + 3 * 4
+        let expr = Expr::add(
+            Expr::number(2.0),
+            Expr::mul(Expr::number(3.0), Expr::number(4.0)),
+        );
+        assert_eq!(
+            expr.pretty_print_tree(),
+            "Add\n├─ 2\n└─ Mul\n   ├─ 3\n   └─ 4\n"
+        );
+
+        // -(1 - 2) / 3
+        let expr = Expr::div(
+            Expr::neg(Expr::sub(Expr::number(1.0), Expr::number(2.0))),
+            Expr::number(3.0),
+        );
+        assert_eq!(
+            expr.pretty_print_tree(),
+            "Div\n├─ Neg\n│  └─ Sub\n│     ├─ 1\n│     └─ 2\n└─ 3\n"
+        );
+    }
+
+    #[test]
+    fn test_minimal_associativity() {
+        // 10 - (2 - 3) keeps its parentheses, (10 - 2) - 3 does not
+        let right = Expr::sub(
+            Expr::number(10.0),
+            Expr::sub(Expr::number(2.0), Expr::number(3.0)),
+        );
+        let left = Expr::sub(
+            Expr::sub(Expr::number(10.0), Expr::number(2.0)),
+            Expr::number(3.0),
+        );
+        let cfg = config(ParenStyle::Minimal, false);
+        assert_eq!(right.to_infix_with_config(&cfg), "10-(2-3)");
+        assert_eq!(left.to_infix_with_config(&cfg), "10-2-3");
+
+        // ^ associates to the right
+        let pow = Expr::pow(
+            Expr::pow(Expr::number(2.0), Expr::number(3.0)),
+            Expr::number(2.0),
+        );
+        assert_eq!(pow.to_infix_with_config(&cfg), "(2^3)^2");
+
+        // `!!` binds tightest of all and stacks without parentheses
+        let expr = Expr::pow(
+            Expr::double_factorial(Expr::double_factorial(Expr::number(5.0))),
+            Expr::double_factorial(Expr::neg(Expr::number(3.0))),
+        );
+        assert_eq!(expr.to_infix_with_config(&cfg), "5!!!!^(-3)!!");
+    }
+
+    #[test]
+    fn test_let() {
+        let cfg = config(ParenStyle::Minimal, true);
+        let square = Expr::let_in(
+            "r",
+            Expr::number(5.0),
+            Expr::mul(Expr::var("r"), Expr::var("r")),
+        );
+        assert_eq!(square.to_infix_with_config(&cfg), "let r = 5 in r * r");
+
+        // A binding used as an operand, a value or an argument is grouped
+        let expr = Expr::add(
+            Expr::let_in("x", square.clone(), Expr::var("x")),
+            Expr::number(1.0),
+        );
+        assert_eq!(
+            expr.to_infix_with_config(&cfg),
+            "(let x = (let r = 5 in r * r) in x) + 1"
+        );
+        let expr = Expr::call("sqrt", vec![square]);
+        assert_eq!(
+            expr.to_infix_with_config(&cfg),
+            "sqrt((let r = 5 in r * r))"
+        );
+    }
+
+    #[test]
+    fn test_format_result() {
+        let cases = [
+            (1.0 / 3.0, "0.333333333333333"),
+            (2.0, "2"),
+            (-2.5, "-2.5"),
+            (0.1 + 0.2, "0.3"),
+            (-0.0, "0"),
+            (123456789012345.0, "123456789012345"),
+            (1e15, "1e+15"),
+            (2.0 / 3.0 * 1e20, "6.66666666666667e+19"),
+            (0.0001, "0.0001"),
+            (0.00001, "1e-05"),
+            (1.5e-7, "1.5e-07"),
+            (999999999999999.9, "1e+15"),
+            (1e-300, "1e-300"),
+            (1.5e300, "1.5e+300"),
+            (f64::INFINITY, "inf"),
+            (f64::NEG_INFINITY, "-inf"),
+            (f64::NAN, "nan"),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(format_result(value), expected, "{:?}", value);
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+
+use crate::ast::{EvalConfig, Expr};
+use crate::error::EvalError;
+
+/// The result of [`Expr::eval_or_residual`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalOutcome {
+    /// Every variable was defined, so the expression has a value.
+    Value(f64),
+    /// What is left of the expression once everything that does not depend
+    /// on an undefined variable has been evaluated.
+    Residual(Expr),
+}
+
+impl Expr {
+    /// Evaluates the expression as far as `env` allows. With every variable
+    /// defined this is `eval_with`; otherwise, each subexpression that does
+    /// not depend on an undefined variable is replaced by its value, and
+    /// the remaining tree is returned.
+    ///
+    /// Folding follows the tree's structure and does not reassociate, so
+    /// `(2 + 3) * x` leaves `5 * x` but `2 + x + 3` is left as it is. Errors
+    /// in the parts that can be evaluated, such as a division by zero, are
+    /// still reported.
+    pub fn eval_or_residual(&self, env: &HashMap<String, f64>) -> Result<EvalOutcome, EvalError> {
+        self.residual(env, &EvalConfig::default())
+    }
+
+    fn residual(
+        &self,
+        env: &HashMap<String, f64>,
+        config: &EvalConfig,
+    ) -> Result<EvalOutcome, EvalError> {
+        match self {
+            Expr::Var(name) if !env.contains_key(name) => Ok(EvalOutcome::Residual(self.clone())),
+            // The body sees the binding only if its value is known; a
+            // residual value hides any outer variable of the same name.
+            Expr::Let { name, value, body } => {
+                let mut scope = env.clone();
+                match value.residual(env, config)? {
+                    EvalOutcome::Value(v) => {
+                        scope.insert(name.clone(), v);
+                        body.residual(&scope, config)
+                    }
+                    EvalOutcome::Residual(value) => {
+                        scope.remove(name);
+                        let body = match body.residual(&scope, config)? {
+                            EvalOutcome::Value(v) => Expr::number(v),
+                            EvalOutcome::Residual(body) => body,
+                        };
+                        Ok(EvalOutcome::Residual(Expr::let_in(
+                            name.clone(),
+                            value,
+                            body,
+                        )))
+                    }
+                }
+            }
+            _ => {
+                let children = self
+                    .children()
+                    .into_iter()
+                    .map(|child| child.residual(env, config))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if let Some(values) = children
+                    .iter()
+                    .map(|child| match child {
+                        EvalOutcome::Value(v) => Some(*v),
+                        EvalOutcome::Residual(_) => None,
+                    })
+                    .collect::<Option<Vec<_>>>()
+                {
+                    return self
+                        .apply_node(&values, env, config)
+                        .map(EvalOutcome::Value);
+                }
+                let children = children
+                    .into_iter()
+                    .map(|child| match child {
+                        EvalOutcome::Value(v) => Expr::number(v),
+                        EvalOutcome::Residual(expr) => expr,
+                    })
+                    .collect();
+                Ok(EvalOutcome::Residual(self.with_children(children)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn parse(input: &str) -> Expr {
+        Parser::new(input).unwrap().parse().unwrap()
+    }
+
+    #[test]
+    fn test_eval_or_residual() {
+        let env = HashMap::from([("y".to_string(), 4.0)]);
+        let outcome = |input| parse(input).eval_or_residual(&env).unwrap();
+
+        assert_eq!(outcome("2 + 3"), EvalOutcome::Value(5.0));
+        assert_eq!(outcome("2 + y"), EvalOutcome::Value(6.0));
+        assert_eq!(outcome("2 + x"), EvalOutcome::Residual(parse("2 + x")));
+        assert_eq!(
+            outcome("(2 + 3) * x - sqrt(y)"),
+            EvalOutcome::Residual(parse("5 * x - 2"))
+        );
+        assert_eq!(
+            outcome("max(x, 1 + 1)"),
+            EvalOutcome::Residual(parse("max(x, 2)"))
+        );
+
+        // Errors in the resolvable parts are still reported
+        assert!(matches!(
+            parse("x + 1 / 0").eval_or_residual(&env),
+            Err(EvalError::DivisionByZero { .. })
+        ));
+    }
+
+    #[test]
+    fn test_eval_or_residual_let() {
+        let env = HashMap::from([("y".to_string(), 4.0)]);
+        let outcome = |input| parse(input).eval_or_residual(&env).unwrap();
+
+        assert_eq!(outcome("let x = 2 in x * y"), EvalOutcome::Value(8.0));
+        assert_eq!(
+            outcome("let a = x in a + y * 2"),
+            EvalOutcome::Residual(parse("let a = x in a + 8"))
+        );
+        // The residual binding shadows the outer `y`
+        assert_eq!(
+            outcome("let y = x in y + 1"),
+            EvalOutcome::Residual(parse("let y = x in y + 1"))
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use crate::ast::Expr;
+
+impl Expr {
+    /// Rewrites every `a - b` into `a + (-b)`, leaving other nodes alone.
+    pub fn normalize_subtraction(&self) -> Expr {
+        match self {
+            Expr::Number(n) => Expr::Number(*n),
+            Expr::Var(name) => Expr::Var(name.clone()),
+            Expr::Call(name, args) => Expr::call(
+                name.clone(),
+                args.iter().map(Expr::normalize_subtraction).collect(),
+            ),
+            Expr::Sub(a, b) => Expr::add(
+                a.normalize_subtraction(),
+                Expr::neg(b.normalize_subtraction()),
+            ),
+            Expr::Add(a, b) => Expr::add(a.normalize_subtraction(), b.normalize_subtraction()),
+            Expr::Mul(a, b) => Expr::mul(a.normalize_subtraction(), b.normalize_subtraction()),
+            Expr::Div(a, b) => Expr::div(a.normalize_subtraction(), b.normalize_subtraction()),
+            Expr::Pow(a, b) => Expr::pow(a.normalize_subtraction(), b.normalize_subtraction()),
+            Expr::Neg(a) => Expr::neg(a.normalize_subtraction()),
+            Expr::DoubleFactorial(a) => Expr::double_factorial(a.normalize_subtraction()),
+            Expr::Range(a, b) => Expr::range(a.normalize_subtraction(), b.normalize_subtraction()),
+            Expr::Let { name, value, body } => Expr::let_in(
+                name.clone(),
+                value.normalize_subtraction(),
+                body.normalize_subtraction(),
+            ),
+        }
+    }
+
+    /// The inverse of [`Expr::normalize_subtraction`]: folds every
+    /// `a + (-b)` back into `a - b`.
+    pub fn denormalize_subtraction(&self) -> Expr {
+        match self {
+            Expr::Number(n) => Expr::Number(*n),
+            Expr::Var(name) => Expr::Var(name.clone()),
+            Expr::Call(name, args) => Expr::call(
+                name.clone(),
+                args.iter().map(Expr::denormalize_subtraction).collect(),
+            ),
+            Expr::Add(a, b) => match b.as_ref() {
+                Expr::Neg(b) => Expr::sub(a.denormalize_subtraction(), b.denormalize_subtraction()),
+                _ => Expr::add(a.denormalize_subtraction(), b.denormalize_subtraction()),
+            },
+            Expr::Sub(a, b) => Expr::sub(a.denormalize_subtraction(), b.denormalize_subtraction()),
+            Expr::Mul(a, b) => Expr::mul(a.denormalize_subtraction(), b.denormalize_subtraction()),
+            Expr::Div(a, b) => Expr::div(a.denormalize_subtraction(), b.denormalize_subtraction()),
+            Expr::Pow(a, b) => Expr::pow(a.denormalize_subtraction(), b.denormalize_subtraction()),
+            Expr::Neg(a) => Expr::neg(a.denormalize_subtraction()),
+            Expr::DoubleFactorial(a) => Expr::double_factorial(a.denormalize_subtraction()),
+            Expr::Range(a, b) => {
+                Expr::range(a.denormalize_subtraction(), b.denormalize_subtraction())
+            }
+            Expr::Let { name, value, body } => Expr::let_in(
+                name.clone(),
+                value.denormalize_subtraction(),
+                body.denormalize_subtraction(),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn n(value: f64) -> Expr {
+        Expr::number(value)
+    }
+
+    #[test]
+    fn test_normalize_subtraction() {
+        // 10 - 2 * (4 - 1)
+        let expr = Expr::sub(n(10.0), Expr::mul(n(2.0), Expr::sub(n(4.0), n(1.0))));
+        let normalized = expr.normalize_subtraction();
+        assert_eq!(
+            normalized,
+            Expr::add(
+                n(10.0),
+                Expr::neg(Expr::mul(n(2.0), Expr::add(n(4.0), Expr::neg(n(1.0))))),
+            )
+        );
+        assert_eq!(normalized.eval().unwrap(), expr.eval().unwrap());
+    }
+
+    #[test]
+    fn test_denormalize_subtraction() {
+        let expr = Expr::sub(Expr::sub(n(10.0), n(2.0)), n(3.0));
+        let roundtrip = expr.normalize_subtraction().denormalize_subtraction();
+        assert_eq!(roundtrip, expr);
+        assert_eq!(roundtrip.eval().unwrap(), 5.0);
+    }
+}
@@ -0,0 +1,90 @@
+use crate::ast::Expr;
+use crate::error::{Location, ParseError};
+
+impl Expr {
+    /// Builds an expression from tokens in reverse Polish notation, e.g.
+    /// `["2", "3", "4", "*", "+"]` for `2 + 3 * 4`.
+    ///
+    /// Tokens are numbers, the binary operators `+ - * / ^`, and `neg` for
+    /// unary minus. Since the tokens carry no source positions, error
+    /// locations report the 1-based index of the offending token as the
+    /// column.
+    pub fn from_reverse_polish(tokens: &[&str]) -> Result<Expr, ParseError> {
+        let mut stack = Vec::new();
+        for (index, &token) in tokens.iter().enumerate() {
+            let location = Location::new(1, index + 1);
+            let mut pop = || {
+                stack.pop().ok_or_else(|| {
+                    ParseError::new(format!("Not enough operands for '{}'", token), location)
+                })
+            };
+            let expr = match token {
+                "neg" => Expr::neg(pop()?),
+                "+" | "-" | "*" | "/" | "^" => {
+                    let b = pop()?;
+                    let a = pop()?;
+                    match token {
+                        "+" => Expr::add(a, b),
+                        "-" => Expr::sub(a, b),
+                        "*" => Expr::mul(a, b),
+                        "/" => Expr::div(a, b),
+                        _ => Expr::pow(a, b),
+                    }
+                }
+                _ => match token.parse::<f64>() {
+                    Ok(n) if n.is_finite() => Expr::number(n),
+                    _ => {
+                        return Err(ParseError::new(
+                            format!("Unknown RPN token: '{}'", token),
+                            location,
+                        ));
+                    }
+                },
+            };
+            stack.push(expr);
+        }
+
+        let end = Location::new(1, tokens.len() + 1);
+        match stack.len() {
+            0 => Err(ParseError::new("empty expression", end)),
+            1 => Ok(stack.pop().expect("one operand")),
+            n => Err(ParseError::new(
+                format!("Malformed RPN: {} operands left without an operator", n),
+                end,
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_from_reverse_polish() {
+        let expr = Expr::from_reverse_polish(&["2", "3", "4", "*", "+"]).unwrap();
+        assert_eq!(expr, Parser::new("2 + 3 * 4").unwrap().parse().unwrap());
+        assert_eq!(expr.eval().unwrap(), 14.0);
+
+        let expr = Expr::from_reverse_polish(&["10", "4", "-", "neg", "2", "/"]).unwrap();
+        assert_eq!(expr, Parser::new("-(10 - 4) / 2").unwrap().parse().unwrap());
+    }
+
+    #[test]
+    fn test_from_reverse_polish_errors() {
+        let err = Expr::from_reverse_polish(&["2", "+"]).unwrap_err();
+        assert_eq!(err.message, "Not enough operands for '+'");
+        assert_eq!(err.location, Location::new(1, 2));
+
+        let err = Expr::from_reverse_polish(&["1", "2", "3", "+"]).unwrap_err();
+        assert_eq!(
+            err.message,
+            "Malformed RPN: 2 operands left without an operator"
+        );
+
+        let err = Expr::from_reverse_polish(&["1", "inf", "+"]).unwrap_err();
+        assert_eq!(err.message, "Unknown RPN token: 'inf'");
+        assert!(Expr::from_reverse_polish(&[]).is_err());
+    }
+}
@@ -0,0 +1,265 @@
+use crate::ast::Expr;
+use crate::error::{Location, ParseError};
+
+impl Expr {
+    /// Renders the expression as an S-expression, e.g. `(+ 2 (* 3 4))`.
+    ///
+    /// Negation is the one-argument form `(- x)`; subtraction is `(- a b)`.
+    /// A function call lists its name and arguments, as in `(max 1 2)`, and
+    /// a binding is `(let name value body)`.
+    pub fn to_sexpr(&self) -> String {
+        match self {
+            Expr::Number(n) => n.to_string(),
+            Expr::Var(name) => name.clone(),
+            Expr::Call(name, args) => {
+                let mut out = format!("({}", name);
+                for arg in args {
+                    out.push(' ');
+                    out.push_str(&arg.to_sexpr());
+                }
+                out.push(')');
+                out
+            }
+            Expr::Add(a, b) => format!("(+ {} {})", a.to_sexpr(), b.to_sexpr()),
+            Expr::Sub(a, b) => format!("(- {} {})", a.to_sexpr(), b.to_sexpr()),
+            Expr::Mul(a, b) => format!("(* {} {})", a.to_sexpr(), b.to_sexpr()),
+            Expr::Div(a, b) => format!("(/ {} {})", a.to_sexpr(), b.to_sexpr()),
+            Expr::Pow(a, b) => format!("(^ {} {})", a.to_sexpr(), b.to_sexpr()),
+            Expr::Neg(a) => format!("(- {})", a.to_sexpr()),
+            Expr::DoubleFactorial(a) => format!("(!! {})", a.to_sexpr()),
+            Expr::Range(a, b) => format!("(.. {} {})", a.to_sexpr(), b.to_sexpr()),
+            Expr::Let { name, value, body } => {
+                format!("(let {} {} {})", name, value.to_sexpr(), body.to_sexpr())
+            }
+        }
+    }
+
+    /// Reads an expression written by [`Expr::to_sexpr`].
+    pub fn from_sexpr(input: &str) -> Result<Expr, ParseError> {
+        let mut reader = SexprReader::new(input);
+        let expr = reader.expr()?;
+        match reader.next_token() {
+            (SexprToken::Eof, _) => Ok(expr),
+            (token, location) => Err(ParseError::new(
+                format!("Expected end of input, got {:?}", token),
+                location,
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum SexprToken<'a> {
+    LeftParen,
+    RightParen,
+    Atom(&'a str),
+    Eof,
+}
+
+struct SexprReader<'a> {
+    input: &'a str,
+    pos: usize,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> SexprReader<'a> {
+    fn new(input: &'a str) -> Self {
+        SexprReader {
+            input,
+            pos: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn advance_char(&mut self) {
+        if let Some(c) = self.input[self.pos..].chars().next() {
+            self.pos += c.len_utf8();
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+    }
+
+    fn next_token(&mut self) -> (SexprToken<'a>, Location) {
+        while self.input[self.pos..].starts_with(char::is_whitespace) {
+            self.advance_char();
+        }
+
+        let location = Location::new(self.line, self.column);
+        let token = match self.input[self.pos..].chars().next() {
+            None => SexprToken::Eof,
+            Some('(') => {
+                self.advance_char();
+                SexprToken::LeftParen
+            }
+            Some(')') => {
+                self.advance_char();
+                SexprToken::RightParen
+            }
+            Some(_) => {
+                let start = self.pos;
+                while self.input[self.pos..]
+                    .starts_with(|c: char| !c.is_whitespace() && c != '(' && c != ')')
+                {
+                    self.advance_char();
+                }
+                SexprToken::Atom(&self.input[start..self.pos])
+            }
+        };
+        (token, location)
+    }
+
+    fn expr(&mut self) -> Result<Expr, ParseError> {
+        match self.next_token() {
+            (SexprToken::Atom(atom), _) if is_identifier(atom) => Ok(Expr::var(atom)),
+            (SexprToken::Atom(atom), location) => atom
+                .parse()
+                .map(Expr::Number)
+                .map_err(|_| ParseError::new(format!("Invalid number: {}", atom), location)),
+            (SexprToken::LeftParen, _) => self.list(),
+            (token, location) => Err(ParseError::new(
+                format!("Expected expression, got {:?}", token),
+                location,
+            )),
+        }
+    }
+
+    // Reads the remainder of a list after its opening parenthesis.
+    fn list(&mut self) -> Result<Expr, ParseError> {
+        let (op, op_location) = match self.next_token() {
+            (SexprToken::Atom(op), location) => (op, location),
+            (token, location) => {
+                return Err(ParseError::new(
+                    format!("Expected operator, got {:?}", token),
+                    location,
+                ));
+            }
+        };
+
+        let mut args = Vec::new();
+        loop {
+            let checkpoint = (self.pos, self.line, self.column);
+            match self.next_token() {
+                (SexprToken::RightParen, _) => break,
+                (SexprToken::Eof, location) => {
+                    return Err(ParseError::new("Expected RightParen, got Eof", location));
+                }
+                _ => {
+                    (self.pos, self.line, self.column) = checkpoint;
+                    args.push(self.expr()?);
+                }
+            }
+        }
+
+        let arity_error = || {
+            ParseError::new(
+                format!("Wrong number of arguments for '{}': {}", op, args.len()),
+                op_location,
+            )
+        };
+        match (op, args.len()) {
+            ("-", 1) => Ok(Expr::neg(args.remove(0))),
+            ("!!", 1) => Ok(Expr::double_factorial(args.remove(0))),
+            ("!!", _) => Err(arity_error()),
+            ("+" | "-" | "*" | "/" | "^" | "..", 2) => {
+                let b = args.pop().unwrap();
+                let a = args.pop().unwrap();
+                Ok(match op {
+                    "+" => Expr::add(a, b),
+                    "-" => Expr::sub(a, b),
+                    "*" => Expr::mul(a, b),
+                    "/" => Expr::div(a, b),
+                    ".." => Expr::range(a, b),
+                    _ => Expr::pow(a, b),
+                })
+            }
+            ("+" | "-" | "*" | "/" | "^" | "..", _) => Err(arity_error()),
+            ("let", 3) => {
+                let body = args.pop().unwrap();
+                let value = args.pop().unwrap();
+                match args.pop().unwrap() {
+                    Expr::Var(name) => Ok(Expr::let_in(name, value, body)),
+                    _ => Err(ParseError::new("Expected a name to bind", op_location)),
+                }
+            }
+            ("let", _) => Err(arity_error()),
+            _ if is_identifier(op) => Ok(Expr::call(op, args)),
+            _ => Err(ParseError::new(
+                format!("Unknown operator: '{}'", op),
+                op_location,
+            )),
+        }
+    }
+}
+
+/// Whether `atom` is a variable name, spelled as the infix lexer reads
+/// them.
+fn is_identifier(atom: &str) -> bool {
+    atom.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && atom.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_sexpr_roundtrip() {
+        let expr = Parser::new("2 + 3 * 4").unwrap().parse().unwrap();
+        let sexpr = expr.to_sexpr();
+        assert_eq!(sexpr, "(+ 2 (* 3 4))");
+
+        let parsed = Expr::from_sexpr(&sexpr).expect("Failed to read S-expression");
+        assert_eq!(parsed, expr);
+        assert_eq!(parsed.eval().unwrap(), 14.0);
+
+        let expr = Parser::new("0..-1").unwrap().parse().unwrap();
+        assert_eq!(expr.to_sexpr(), "(.. 0 (- 1))");
+        assert_eq!(Expr::from_sexpr(&expr.to_sexpr()).unwrap(), expr);
+
+        let expr = Parser::new("x * (y_2 - x)").unwrap().parse().unwrap();
+        assert_eq!(expr.to_sexpr(), "(* x (- y_2 x))");
+        assert_eq!(Expr::from_sexpr(&expr.to_sexpr()).unwrap(), expr);
+
+        let expr = Parser::new("max(sqrt(x), 2)").unwrap().parse().unwrap();
+        assert_eq!(expr.to_sexpr(), "(max (sqrt x) 2)");
+        assert_eq!(Expr::from_sexpr(&expr.to_sexpr()).unwrap(), expr);
+
+        let expr = Parser::new("let r = 5 in r * r").unwrap().parse().unwrap();
+        assert_eq!(expr.to_sexpr(), "(let r 5 (* r r))");
+        assert_eq!(Expr::from_sexpr(&expr.to_sexpr()).unwrap(), expr);
+    }
+
+    #[test]
+    fn test_sexpr_negation() {
+        let expr = Expr::sub(Expr::neg(Expr::number(1.5)), Expr::number(2.0));
+        assert_eq!(expr.to_sexpr(), "(- (- 1.5) 2)");
+        assert_eq!(Expr::from_sexpr(&expr.to_sexpr()).unwrap(), expr);
+
+        let expr = Expr::double_factorial(Expr::number(7.0));
+        assert_eq!(expr.to_sexpr(), "(!! 7)");
+        assert_eq!(Expr::from_sexpr(&expr.to_sexpr()).unwrap(), expr);
+    }
+
+    #[test]
+    fn test_sexpr_errors() {
+        let err = Expr::from_sexpr("(+ 1)").unwrap_err();
+        assert!(err.message.contains("Wrong number of arguments"));
+
+        let err = Expr::from_sexpr("(% 1 2)").unwrap_err();
+        assert_eq!(err.location.column, 2);
+
+        let err = Expr::from_sexpr("(+ 1 2").unwrap_err();
+        assert!(err.message.contains("Expected RightParen"));
+
+        assert!(Expr::from_sexpr("(+ 1 2) 3").is_err());
+        assert!(Expr::from_sexpr("(let 1 2 3)").is_err());
+        assert!(Expr::from_sexpr("(let x 2)").is_err());
+    }
+}
@@ -0,0 +1,440 @@
+use crate::ast::Expr;
+use crate::error::{Location, ParseError};
+use crate::lexer::Lexer;
+use crate::token::Token;
+
+/// An operator waiting on the shunting-yard stack.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StackOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    Neg,
+    Range,
+    LeftParen,
+    /// The `(` opening a function call's argument list.
+    Call,
+    /// `let name =`, waiting for the `in` that ends the bound value.
+    LetValue,
+    /// `in`, whose body runs to the closing `)` or the end of input.
+    LetBody,
+}
+
+impl StackOp {
+    fn precedence(self) -> u8 {
+        match self {
+            StackOp::LeftParen | StackOp::Call | StackOp::LetValue | StackOp::LetBody => 0,
+            StackOp::Range => 1,
+            StackOp::Add | StackOp::Sub => 2,
+            StackOp::Mul | StackOp::Div => 3,
+            StackOp::Neg => 4,
+            StackOp::Pow => 5,
+        }
+    }
+}
+
+/// Parses `input` with Dijkstra's shunting-yard algorithm.
+///
+/// This is an independent implementation of the grammar accepted by
+/// [`Parser`](crate::parser::Parser) with its default configuration, kept
+/// as a reference to cross-check the recursive-descent parser: for every
+/// valid input both must produce the same tree. Error messages and
+/// locations are not guaranteed to match.
+pub fn parse_shunting_yard(input: &str) -> Result<Expr, ParseError> {
+    let mut lexer = Lexer::new(input);
+    let mut output: Vec<Expr> = Vec::new();
+    let mut operators: Vec<(StackOp, Location)> = Vec::new();
+    // The name and number of completed arguments of each open call, in
+    // step with the `StackOp::Call` markers on `operators`.
+    let mut calls: Vec<(String, usize)> = Vec::new();
+    // The names bound by the open `let`s, in step with their markers.
+    let mut lets: Vec<String> = Vec::new();
+    // True where the grammar needs an operand: at the start, after an
+    // operator and after `(`.
+    let mut expect_operand = true;
+    let mut saw_token = false;
+
+    loop {
+        let (token, location) = lexer.next_token()?;
+        match token {
+            Token::Number { value, .. } => {
+                if !expect_operand {
+                    return Err(missing_operator(location));
+                }
+                output.push(Expr::number(value));
+                expect_operand = false;
+            }
+            Token::Identifier(name) => {
+                if !expect_operand {
+                    return Err(missing_operator(location));
+                }
+                // A `(` directly after the name opens a call
+                let call_paren = location.advance_column(name.len());
+                let mut lookahead = lexer.clone();
+                if lookahead.next_token()? == (Token::LeftParen, call_paren) {
+                    lexer = lookahead;
+                    operators.push((StackOp::Call, call_paren));
+                    calls.push((name, 0));
+                } else {
+                    output.push(Expr::Var(name));
+                    expect_operand = false;
+                }
+            }
+            Token::LeftParen => {
+                if !expect_operand {
+                    return Err(missing_operator(location));
+                }
+                operators.push((StackOp::LeftParen, location));
+            }
+            // A binding may only start where a range may: at the start of
+            // the input, of a parenthesized group or of another binding's
+            // body.
+            Token::Let
+                if expect_operand
+                    && matches!(
+                        operators.last(),
+                        None | Some((StackOp::LeftParen | StackOp::LetBody, _))
+                    ) =>
+            {
+                let (name, name_location) = lexer.next_token()?;
+                let Token::Identifier(name) = name else {
+                    return Err(ParseError::new(
+                        format!("Expected a name after let, got {:?}", name),
+                        name_location,
+                    ));
+                };
+                let (equals, equals_location) = lexer.next_token()?;
+                if equals != Token::Equals {
+                    return Err(ParseError::new(
+                        format!("Expected Equals, got {:?}", equals),
+                        equals_location,
+                    ));
+                }
+                operators.push((StackOp::LetValue, location));
+                lets.push(name);
+            }
+            Token::In => {
+                if expect_operand {
+                    return Err(expected_expression(&token, location));
+                }
+                loop {
+                    match operators.pop() {
+                        Some((StackOp::LetValue, let_location)) => {
+                            operators.push((StackOp::LetBody, let_location));
+                            break;
+                        }
+                        Some((
+                            op @ (StackOp::Add
+                            | StackOp::Sub
+                            | StackOp::Mul
+                            | StackOp::Div
+                            | StackOp::Pow
+                            | StackOp::Neg),
+                            _,
+                        )) => apply(op, &mut output, &mut lets),
+                        _ => {
+                            return Err(ParseError::new("Expected end of input, got In", location));
+                        }
+                    }
+                }
+                expect_operand = true;
+            }
+            Token::Let | Token::Equals => {
+                return Err(ParseError::new(
+                    format!("Expected expression, got {:?}", token),
+                    location,
+                ));
+            }
+            // A postfix operator applies at once to the operand before it,
+            // binding tighter than anything still on the stack
+            Token::DoubleBang => {
+                if expect_operand {
+                    return Err(expected_expression(&token, location));
+                }
+                let operand = output.pop().expect("an operand precedes `!!`");
+                output.push(Expr::double_factorial(operand));
+            }
+            Token::Minus if expect_operand => operators.push((StackOp::Neg, location)),
+            Token::Plus
+            | Token::Minus
+            | Token::Star
+            | Token::Slash
+            | Token::Caret
+            | Token::DotDot => {
+                if expect_operand {
+                    return Err(expected_expression(&token, location));
+                }
+                let op = match token {
+                    Token::Plus => StackOp::Add,
+                    Token::Minus => StackOp::Sub,
+                    Token::Star => StackOp::Mul,
+                    Token::Slash => StackOp::Div,
+                    Token::Caret => StackOp::Pow,
+                    _ => StackOp::Range,
+                };
+                // The other arithmetic operators are left-associative, so
+                // equal precedence pops too; `^` is right-associative. A
+                // pending prefix `-` binds looser than `^`, so `-2 ^ 2`
+                // negates the power.
+                while let Some(&(top, _)) = operators.last()
+                    && (top.precedence() > op.precedence()
+                        || (top.precedence() == op.precedence() && op != StackOp::Pow))
+                    && top != StackOp::Range
+                {
+                    operators.pop();
+                    apply(top, &mut output, &mut lets);
+                }
+                // Ranges do not chain, and are neither function arguments
+                // nor bound values
+                if op == StackOp::Range {
+                    match operators.last() {
+                        Some((StackOp::Range, _)) => {
+                            return Err(ParseError::new(
+                                "Expected end of input, got DotDot",
+                                location,
+                            ));
+                        }
+                        Some((StackOp::Call, _)) => {
+                            return Err(ParseError::new(
+                                "Expected RightParen, got DotDot",
+                                location,
+                            ));
+                        }
+                        Some((StackOp::LetValue, _)) => {
+                            return Err(ParseError::new("Expected In, got DotDot", location));
+                        }
+                        _ => {}
+                    }
+                }
+                operators.push((op, location));
+                expect_operand = true;
+            }
+            Token::Comma => {
+                if expect_operand {
+                    return Err(expected_expression(&token, location));
+                }
+                loop {
+                    match operators.last() {
+                        Some((StackOp::Call, _)) => break,
+                        Some((StackOp::LeftParen, _)) => {
+                            return Err(ParseError::new(
+                                "Expected RightParen, got Comma",
+                                location,
+                            ));
+                        }
+                        Some((StackOp::LetValue, _)) => {
+                            return Err(ParseError::new("Expected In, got Comma", location));
+                        }
+                        Some(&(op, _)) => {
+                            operators.pop();
+                            apply(op, &mut output, &mut lets);
+                        }
+                        None => {
+                            return Err(ParseError::new(
+                                "Expected end of input, got Comma",
+                                location,
+                            ));
+                        }
+                    }
+                }
+                calls.last_mut().expect("a call is open").1 += 1;
+                expect_operand = true;
+            }
+            Token::RightParen => {
+                // Only an empty argument list closes with no operand
+                let empty_call = matches!(operators.last(), Some((StackOp::Call, _)))
+                    && calls.last().is_some_and(|&(_, args)| args == 0);
+                if expect_operand && !empty_call {
+                    return Err(expected_expression(&token, location));
+                }
+                loop {
+                    match operators.pop() {
+                        Some((StackOp::LeftParen, _)) => break,
+                        Some((StackOp::Call, _)) => {
+                            let (name, completed) = calls.pop().expect("a call is open");
+                            let arity = if expect_operand { 0 } else { completed + 1 };
+                            let args = output.split_off(output.len() - arity);
+                            output.push(Expr::call(name, args));
+                            expect_operand = false;
+                            break;
+                        }
+                        Some((StackOp::LetValue, _)) => {
+                            return Err(ParseError::new("Expected In, got RightParen", location));
+                        }
+                        Some((op, _)) => apply(op, &mut output, &mut lets),
+                        None => {
+                            return Err(ParseError::new(
+                                "Expected end of input, got RightParen",
+                                location,
+                            ));
+                        }
+                    }
+                }
+            }
+            Token::Eof => {
+                if !saw_token {
+                    return Err(ParseError::new("empty expression", location));
+                }
+                if expect_operand {
+                    return Err(expected_expression(&token, location));
+                }
+                while let Some((op, _)) = operators.pop() {
+                    match op {
+                        StackOp::LeftParen | StackOp::Call => {
+                            return Err(ParseError::new("Expected RightParen, got Eof", location));
+                        }
+                        StackOp::LetValue => {
+                            return Err(ParseError::new("Expected In, got Eof", location));
+                        }
+                        _ => apply(op, &mut output, &mut lets),
+                    }
+                }
+                return Ok(output
+                    .pop()
+                    .expect("a complete expression leaves one operand"));
+            }
+        }
+        saw_token = true;
+    }
+}
+
+/// Pops the operands of `op` off `output` and pushes the combined node. A
+/// `let` also takes its name off `lets`.
+fn apply(op: StackOp, output: &mut Vec<Expr>, lets: &mut Vec<String>) {
+    let rhs = output.pop().expect("operator has an operand");
+    let expr = match op {
+        StackOp::Neg => Expr::neg(rhs),
+        _ => {
+            let lhs = output.pop().expect("binary operator has two operands");
+            match op {
+                StackOp::Add => Expr::add(lhs, rhs),
+                StackOp::Sub => Expr::sub(lhs, rhs),
+                StackOp::Mul => Expr::mul(lhs, rhs),
+                StackOp::Div => Expr::div(lhs, rhs),
+                StackOp::Pow => Expr::pow(lhs, rhs),
+                StackOp::LetBody => {
+                    let name = lets.pop().expect("a let is open");
+                    Expr::let_in(name, lhs, rhs)
+                }
+                _ => Expr::range(lhs, rhs),
+            }
+        }
+    };
+    output.push(expr);
+}
+
+fn missing_operator(location: Location) -> ParseError {
+    ParseError::new("missing operator between operands", location)
+}
+
+fn expected_expression(token: &Token, location: Location) -> ParseError {
+    ParseError::new(format!("Expected expression, got {:?}", token), location)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_shunting_yard_matches_parser() {
+        let inputs = [
+            "42",
+            "1 + 2",
+            "1 - 2 - 3",
+            "8 / 4 / 2",
+            "1 + 2 * 3",
+            "1 * 2 + 3",
+            "(1 + 2) * 3",
+            "2 * (3 - 4) / 5",
+            "-3",
+            "--3",
+            "-(2 + 3)",
+            "-2 * 3",
+            "2 * -3",
+            "1 - -1",
+            "-(-(4))",
+            "((((1))))",
+            "1 + 2 - 3 * 4 / 5 + -6",
+            "(1 - (2 - (3 - 4))) * -(5 / (6 + 7))",
+            ".5 * 2.25 - 10",
+            "0..10",
+            "1 + 2..3 * 4",
+            "(0..1)",
+            "2 ^ 3",
+            "2 ^ 3 ^ 2",
+            "-2 ^ 2",
+            "2 ^ -1 * 3",
+            "2 ^ -3 ^ 2",
+            "(1 + 1) ^ 3 / 4",
+            "x + 2 * y",
+            "-x ^ n_1",
+            "sqrt(16)",
+            "max(1, 2) * -abs(x - 3)",
+            "f()",
+            "f(g(1, 2 + 3), -h(x) ^ 2, (4))",
+            "2 ^ max(1, 2) ^ 3",
+            "let r = 5 in r * r",
+            "let a = 1 in let b = -a ^ 2 in a + b * 2",
+            "(let x = 2 in x) + sqrt((let y = 1 in y..2))",
+            "let n = 3 in 0..n",
+            "5!!",
+            "-3!! ^ 2!!!! * (1 + 2)!!",
+            "f(x)!! + x!!",
+        ];
+        for input in inputs {
+            let expected = Parser::new(input).unwrap().parse().unwrap();
+            assert_eq!(parse_shunting_yard(input).unwrap(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_shunting_yard_rejects_what_parser_rejects() {
+        let inputs = [
+            "",
+            "1 +",
+            "* 2",
+            "(1 + 2",
+            "1 + 2)",
+            "2 3",
+            "2 (3)",
+            "()",
+            "1 @ 2",
+            "1..2..3",
+            "..2",
+            "2 ^",
+            "x y",
+            "f(1,)",
+            "f(,1)",
+            "f(1",
+            "f(0..1)",
+            "(1, 2)",
+            "1, 2",
+            "f (1)",
+            "let x = 1",
+            "let x 1 in x",
+            "let 2 = 1 in 1",
+            "1 + let x = 1 in x",
+            "let x = 0..1 in x",
+            "let x = 1 in",
+            "f(let x = 1 in x)",
+            "(let x = 1) in x",
+            "let x = 1 in 2 in 3",
+            "x = 1",
+            "!!5",
+            "(!!)",
+            "5!!!",
+        ];
+        for input in inputs {
+            assert!(
+                Parser::new(input)
+                    .map_err(ParseError::from)
+                    .and_then(|mut p| p.parse())
+                    .is_err()
+            );
+            assert!(parse_shunting_yard(input).is_err(), "{}", input);
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+use crate::ast::{EvalConfig, Expr};
+
+/// Options for [`Expr::simplify_with_config`]. The default matches
+/// `simplify`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SimplifyConfig {
+    /// Also rewrite `x - x` to `0`, `x / x` to `1` and `x + x` to `2 * x`
+    /// for any subexpression `x`, variables included.
+    ///
+    /// These identities do not hold for every float, so this changes what
+    /// the expression evaluates to when `x` is infinite or NaN: `x - x` and
+    /// `x / x` are then NaN, not `0` and `1`. `x / x` also hides a zero
+    /// divisor, and every rewrite hides an error `x` would evaluate to.
+    pub aggressive: bool,
+}
+
+impl Expr {
+    /// Simplifies the tree bottom-up without needing values for its
+    /// variables: every operation whose operands are all numbers is folded
+    /// into a number, and the identities `x + 0`, `0 + x`, `x - 0`,
+    /// `x * 1`, `1 * x` and `x ^ 1` (to `x`) and `x * 0`, `0 * x` (to `0`)
+    /// are applied. So `(2 * 3) + (x * 1)` becomes `6 + x`.
+    ///
+    /// An operation that would fail to evaluate, such as `1 / 0`, is left
+    /// unfolded. The identities assume their operand is finite: `x * 0` is
+    /// `0` even where `x` would evaluate to an error.
+    pub fn simplify(&self) -> Expr {
+        self.simplify_with_config(&SimplifyConfig::default())
+    }
+
+    /// Simplifies the tree like [`Expr::simplify`], applying the further
+    /// identities selected by `cfg`.
+    pub fn simplify_with_config(&self, cfg: &SimplifyConfig) -> Expr {
+        let children = self
+            .children()
+            .into_iter()
+            .map(|child| child.simplify_with_config(cfg))
+            .collect();
+        let expr = self.with_children(children);
+        match expr.fold_constant() {
+            Some(value) => Expr::Number(value),
+            None if cfg.aggressive => expr.apply_identities().apply_aggressive_identities(),
+            None => expr.apply_identities(),
+        }
+    }
+
+    fn fold_constant(&self) -> Option<f64> {
+        if matches!(self, Expr::Number(_) | Expr::Var(_) | Expr::Let { .. }) {
+            return None;
+        }
+        let operands = self
+            .children()
+            .into_iter()
+            .map(Expr::as_number)
+            .collect::<Option<Vec<_>>>()?;
+        self.apply_node(&operands, &HashMap::new(), &EvalConfig::default())
+            .ok()
+    }
+
+    fn apply_identities(self) -> Expr {
+        let is = |expr: &Expr, value: f64| expr.as_number() == Some(value);
+        match self {
+            Expr::Add(a, b) if is(&b, 0.0) => *a,
+            Expr::Add(a, b) if is(&a, 0.0) => *b,
+            Expr::Sub(a, b) if is(&b, 0.0) => *a,
+            Expr::Mul(a, b) if is(&a, 0.0) || is(&b, 0.0) => Expr::Number(0.0),
+            Expr::Mul(a, b) if is(&b, 1.0) => *a,
+            Expr::Mul(a, b) if is(&a, 1.0) => *b,
+            Expr::Pow(a, b) if is(&b, 1.0) => *a,
+            expr => expr,
+        }
+    }
+
+    fn apply_aggressive_identities(self) -> Expr {
+        match self {
+            Expr::Sub(a, b) if a == b => Expr::Number(0.0),
+            Expr::Div(a, b) if a == b => Expr::Number(1.0),
+            Expr::Add(a, b) if a == b => Expr::mul(Expr::Number(2.0), *a),
+            expr => expr,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn parse(input: &str) -> Expr {
+        Parser::new(input).unwrap().parse().unwrap()
+    }
+
+    fn simplified(input: &str) -> String {
+        parse(input).simplify().to_string()
+    }
+
+    fn aggressive(input: &str) -> String {
+        let cfg = SimplifyConfig { aggressive: true };
+        parse(input).simplify_with_config(&cfg).to_string()
+    }
+
+    #[test]
+    fn test_simplify_constants() {
+        assert_eq!(simplified("2 * 3 + 4"), "10");
+        assert_eq!(simplified("1 + 2 + x"), "3 + x");
+        assert_eq!(simplified("-(2 ^ 3) * y"), "-8 * y");
+        assert_eq!(simplified("sqrt(16) + x"), "4 + x");
+        // Folding that would fail leaves the operation as it is
+        assert_eq!(simplified("x + 1 / 0"), "x + 1 / 0");
+        assert_eq!(simplified("(2 - 2) + 3 / (1 - 1)"), "3 / 0");
+    }
+
+    #[test]
+    fn test_simplify_identities() {
+        assert_eq!(simplified("x + 0"), "x");
+        assert_eq!(simplified("0 + x"), "x");
+        assert_eq!(simplified("x - 0"), "x");
+        assert_eq!(simplified("x * 1"), "x");
+        assert_eq!(simplified("1 * x"), "x");
+        assert_eq!(simplified("x * 0"), "0");
+        assert_eq!(simplified("0 * x"), "0");
+        assert_eq!(simplified("x ^ 1"), "x");
+        // `0 - x` and `1 / x` have no such identity
+        assert_eq!(simplified("0 - x"), "0 - x");
+        assert_eq!(simplified("x ^ 0"), "x ^ 0");
+    }
+
+    #[test]
+    fn test_simplify_mixed() {
+        assert_eq!(simplified("(2 * 3) + (x * 1)"), "6 + x");
+        // Identities apply to folded operands, bottom-up
+        assert_eq!(simplified("y * (x - x * (3 - 2)) ^ (5 - 4)"), "y * (x - x)");
+        assert_eq!(simplified("(x + (1 - 1)) * (2 - 1)"), "x");
+        assert_eq!(
+            simplified("let r = 2 * 1 in r * r + 0"),
+            "let r = 2 in r * r"
+        );
+    }
+
+    #[test]
+    fn test_simplify_aggressive() {
+        assert_eq!(aggressive("y - y"), "0");
+        assert_eq!(simplified("y - y"), "y - y");
+        assert_eq!(aggressive("x / x"), "1");
+        assert_eq!(simplified("x / x"), "x / x");
+        assert_eq!(aggressive("x + x"), "2 * x");
+        assert_eq!(simplified("x + x"), "x + x");
+        // Subexpressions compare structurally, after simplifying
+        assert_eq!(aggressive("x * y - y * x"), "x * y - y * x");
+        assert_eq!(aggressive("(x * 1) / x + 1"), "2");
+        assert_eq!(aggressive("sqrt(z) + sqrt(z) * 1"), "2 * sqrt(z)");
+        // The derivative of x * x reads as 2 * x
+        let cfg = SimplifyConfig { aggressive: true };
+        let derivative = parse("x * x").derivative("x").simplify_with_config(&cfg);
+        assert_eq!(derivative.to_string(), "2 * x");
+    }
+}
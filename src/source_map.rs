@@ -0,0 +1,106 @@
+use crate::error::{Location, ParseError};
+
+/// Tracks how a source string was assembled from named fragments, so that
+/// a location in the combined source can be mapped back to the fragment it
+/// came from.
+///
+/// Every fragment starts on a new line of the combined source.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    source: String,
+    fragments: Vec<Fragment>,
+}
+
+#[derive(Debug, Clone)]
+struct Fragment {
+    name: String,
+    /// Line of the combined source the fragment starts on.
+    start_line: usize,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends `text` as a fragment called `name`.
+    pub fn add_fragment(&mut self, name: impl Into<String>, text: &str) {
+        if !self.source.is_empty() && !self.source.ends_with('\n') {
+            self.source.push('\n');
+        }
+        let start_line = self.source.matches('\n').count() + 1;
+        self.fragments.push(Fragment {
+            name: name.into(),
+            start_line,
+        });
+        self.source.push_str(text);
+    }
+
+    /// Returns the combined source of all fragments.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Maps a location in the combined source to the name of its fragment
+    /// and the location within that fragment.
+    pub fn lookup(&self, location: Location) -> Option<(&str, Location)> {
+        let fragment = self
+            .fragments
+            .iter()
+            .rev()
+            .find(|fragment| fragment.start_line <= location.line)?;
+        let local = Location::new(location.line - fragment.start_line + 1, location.column);
+        Some((&fragment.name, local))
+    }
+
+    /// Renders a parse error of the combined source in terms of the
+    /// fragment it occurred in.
+    pub fn render_error(&self, err: &ParseError) -> String {
+        match self.lookup(err.location) {
+            Some((name, local)) => format!("Parse error in {} at {}: {}", name, local, err.message),
+            None => err.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_source_map_lookup() {
+        let mut map = SourceMap::new();
+        map.add_fragment("base.expr", "1 +\n2 *");
+        map.add_fragment("extra.expr", "(3 +\n@)");
+        assert_eq!(map.source(), "1 +\n2 *\n(3 +\n@)");
+
+        let err = Parser::new(map.source()).unwrap().parse().unwrap_err();
+        assert_eq!(err.location, Location::new(4, 1));
+        assert_eq!(
+            map.lookup(err.location),
+            Some(("extra.expr", Location::new(2, 1)))
+        );
+        assert_eq!(
+            map.render_error(&err),
+            "Parse error in extra.expr at line 2, column 1: Unexpected character: '@'"
+        );
+    }
+
+    #[test]
+    fn test_source_map_first_fragment() {
+        let mut map = SourceMap::new();
+        map.add_fragment("a", "1 + 2");
+        map.add_fragment("b", "* 3\n");
+        map.add_fragment("c", "+ 4");
+        assert_eq!(
+            map.lookup(Location::new(1, 3)),
+            Some(("a", Location::new(1, 3)))
+        );
+        assert_eq!(
+            map.lookup(Location::new(3, 1)),
+            Some(("c", Location::new(1, 1)))
+        );
+        assert_eq!(map.lookup(Location::new(0, 1)), None);
+    }
+}
@@ -1,11 +1,27 @@
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
-    Number(f64),
+    /// A number literal. `exact` is false when the decimal literal written
+    /// in the source is not exactly representable as an `f64`, as with `0.1`.
+    Number {
+        value: f64,
+        exact: bool,
+    },
+    /// A variable name: a letter or `_` followed by letters, digits or `_`,
+    /// other than the keywords `let` and `in`.
+    Identifier(String),
+    Let,
+    In,
+    Equals,
     Plus,
     Minus,
     Star,
     Slash,
+    Caret,
+    /// The postfix double factorial `!!`. There is no single `!`.
+    DoubleBang,
     LeftParen,
     RightParen,
+    Comma,
+    DotDot,
     Eof,
 }
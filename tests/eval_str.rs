@@ -0,0 +1,66 @@
+use rust_expr_evaluator::{EvalError, ExprError, eval_str, format_result};
+
+#[test]
+fn test_eval_str() {
+    assert_eq!(eval_str("2 + 3").unwrap(), 5.0);
+    assert_eq!(eval_str("2 + 3 * 4").unwrap(), 14.0);
+    assert_eq!(eval_str("(2 + 3) * -4").unwrap(), -20.0);
+    assert_eq!(eval_str("2 ^ 10 / 1e3").unwrap(), 1.024);
+    assert_eq!(eval_str("let r = 3 in max(r * r, sqrt(16))").unwrap(), 9.0);
+}
+
+#[test]
+fn test_eval_str_display() {
+    let display = |input| format_result(eval_str(input).unwrap());
+    assert_eq!(display("1 / 3"), "0.333333333333333");
+    assert_eq!(display("0.1 + 0.2"), "0.3");
+    assert_eq!(display("2 ^ 60"), "1.15292150460685e+18");
+    assert_eq!(display("6 / 3"), "2");
+}
+
+#[test]
+fn test_eval_str_lexer_error() {
+    let err = eval_str("@").unwrap_err();
+    assert!(matches!(err, ExprError::Lexer(_)), "{:?}", err);
+    assert_eq!(
+        err.to_string(),
+        "Lexer error at line 1, column 1: Unexpected character: '@'"
+    );
+}
+
+#[test]
+fn test_eval_str_parse_error() {
+    assert!(matches!(eval_str("(2 + 3"), Err(ExprError::Parse(_))));
+    assert!(matches!(eval_str(""), Err(ExprError::Parse(_))));
+}
+
+#[test]
+fn test_eval_str_later_lexer_error() {
+    // A lexer error after the first token is still a lexer error
+    let err = eval_str("1 + @").unwrap_err();
+    assert!(matches!(err, ExprError::Lexer(_)), "{:?}", err);
+    assert_eq!(
+        err.to_string(),
+        "Lexer error at line 1, column 5: Unexpected character: '@'"
+    );
+}
+
+#[test]
+fn test_eval_str_eval_error() {
+    let err = eval_str("10 / 0").unwrap_err();
+    assert!(matches!(
+        err,
+        ExprError::Eval(EvalError::DivisionByZero { .. })
+    ));
+    assert_eq!(err.to_string(), "Division by zero: 10 / 0");
+    assert!(matches!(
+        eval_str("x + 1"),
+        Err(ExprError::Eval(EvalError::UndefinedVariable(_)))
+    ));
+}
+
+#[test]
+fn test_eval_str_io_error() {
+    let err = std::io::Error::from(eval_str("1 +").unwrap_err());
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}